@@ -1,23 +1,48 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    coin, to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
+    coin, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Decimal, Deps, DepsMut, Env, Event,
+    Int128, MessageInfo, Order, Response, StdError, StdResult, Storage, WasmMsg,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw3::{
+    ProposalListResponse, ProposalResponse, Status as Cw3Status, Vote as Cw3Vote, VoteInfo,
+    VoteListResponse, VoteResponse,
+};
+use cw4::{Cw4QueryMsg, MemberResponse, TotalWeightResponse};
+use cw_storage_plus::Bound;
+use cw_utils::{Expiration, ThresholdResponse};
 
 use crate::error::ContractError;
-use crate::helpers::validate_sent_sufficient_coin;
-use crate::msg::{ExecuteMsg, GetProposalResponse, InstantiateMsg, QueryMsg, TokenStakeResponse};
-use crate::state::{ProposalStatus, State, Voter, BALANCES, PROPOSALS, STATE, VOTERS};
+use crate::helpers::{extract_budget_coin, refund_excess, validate_sent_sufficient_coin};
+use crate::msg::{
+    ConvictionResponse, DelegationResponse, EligibleResponse, EstimatedMatchResponse,
+    EstimatedMatchesResponse, ExecuteMsg, GetProposalResponse, InstantiateMsg, IsVerifiedResponse,
+    MigrateMsg, QueryMsg, RegistryQueryMsg, RoundResultsResponse, SimulateContributionResponse,
+    SnapshotBalanceResponse, SnapshotQueryMsg, StreamResponse, TokenStakeResponse,
+    TreasuryResponse,
+};
+use crate::quadratic::{CalculatedGrant, RawGrant};
+use crate::ranking::RankingMethod;
+use crate::state::{
+    default_milestone_threshold, default_unit_price, BalanceVote, Ballot, BallotMode, Commitment,
+    Conviction, DelegatedVote, Distribution, Escrow, GrantPayout, Milestone, MilestoneStatus,
+    ParameterChange, PlatformFee, Proposal, ProposalStatus, Ranking, RetroFunding, Round, State,
+    Stream, UnrevealedPolicy, Vesting, Voter, VoterEligibility, VotingMode, ALLOCATIONS, ALLOWLIST,
+    BALANCES, BALLOTS, COMMITMENTS, CONVICTIONS, CONVICTION_POOL, CONVICTION_STAKES,
+    DELEGATED_VOTES, DELEGATIONS, DELEGATION_CREDITS, DELEGATORS, DISTRIBUTIONS, ESCROWS,
+    MAX_FEE_BPS, PARAMETER_CHANGES, PARAMETER_REVIEW_PERIOD, PARAMETER_VOTING_PERIOD, PROPOSALS,
+    PROPOSAL_MSGS, REVIEWS, ROUNDS, STATE, STORED_BALANCES, STREAMS, TREASURY, TRUST_WEIGHTS,
+    VOTERS,
+};
 use cosmwasm_std::Uint128;
+use sha2::{Digest, Sha256};
 // version info for migration info
 
 pub const VOTING_TOKEN: &str = "voting_token";
 const CONTRACT_NAME: &str = "crates.io:voting";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-const MIN_STAKE_AMOUNT: u128 = 1000;
-
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -28,48 +53,329 @@ pub fn instantiate(
     let state = State {
         denom: msg.denom,
         owner: info.sender.clone(),
+        reviewers: vec![],
+        ballot_mode: BallotMode::Open,
+        voter_eligibility: VoterEligibility::Open,
+        voting_mode: VotingMode::Quadratic,
+        reviewer_group: None,
+        ranking: None,
+        guardian: None,
+        paused: false,
+        fee: None,
+        milestone_threshold: default_milestone_threshold(),
+        default_trust_weight: Decimal::one(),
+        unit_price: default_unit_price(),
     };
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     STATE.save(deps.storage, &state)?;
 
     Ok(Response::new()
+        .add_event(
+            Event::new("instantiate")
+                .add_attribute("owner", info.sender.clone())
+                .add_attribute("denom", state.denom),
+        )
         .add_attribute("method", "instantiate")
         .add_attribute("owner", info.sender))
 }
 
+// fields added since the stored version are filled by their serde defaults on load,
+// balances with u64 proposal ids are rewritten here
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: stored.contract,
+        });
+    }
+    if parse_version(&stored.version) > parse_version(CONTRACT_VERSION) {
+        return Err(ContractError::CannotMigrateVersion {
+            previous_version: stored.version,
+        });
+    }
+
+    let state = STATE.load(deps.storage)?;
+    STATE.save(deps.storage, &state)?;
+
+    let balances = STORED_BALANCES
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let migrated_balances = balances.len();
+    for (key, balance) in balances {
+        BALANCES.save(deps.storage, &key, &balance.into())?;
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("migrate")
+                .add_attribute("version", CONTRACT_VERSION)
+                .add_attribute("balances", migrated_balances.to_string()),
+        )
+        .add_attribute("method", "migrate"))
+}
+
+// numeric major, minor and patch, so that 0.10.0 sorts after 0.9.0
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| part.parse().unwrap_or_default())
+        .collect()
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    if !allowed_while_paused(&msg) && STATE.load(deps.storage)?.paused {
+        return Err(ContractError::Paused {});
+    }
     match msg {
         ExecuteMsg::Vote {
             proposal_id,
             yes_vote,
-        } => execute::vote(deps, info, proposal_id, yes_vote),
+        } => execute::vote(deps, env, info, proposal_id, yes_vote),
 
         ExecuteMsg::Review {
             proposal_id,
             approved,
         } => execute::review(deps, info, proposal_id, approved),
-        ExecuteMsg::EndVote { proposal_id } => execute::end_vote(deps, info, proposal_id),
-        ExecuteMsg::EndReview { proposal_id } => execute::end_review(deps, info, proposal_id),
-        ExecuteMsg::FundingProposal {
-            proposal_id
-        } => execute::funding_proposal(deps, info, proposal_id),
+        ExecuteMsg::EndVote { proposal_id } => execute::end_vote(deps, env, info, proposal_id),
+        ExecuteMsg::EndReview { proposal_id } => {
+            execute::end_review(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::FundingProposal { round_id } => {
+            execute::funding_proposal(deps, info, round_id)
+        }
+        ExecuteMsg::ClaimGrant { round_id } => execute::claim_grant(deps, info, round_id),
+        ExecuteMsg::CreateRound {
+            round_id,
+            proposals,
+            algorithm,
+            milestones_required,
+            vesting,
+            retro,
+        } => execute::create_round(
+            deps,
+            env,
+            info,
+            round_id,
+            proposals,
+            algorithm,
+            milestones_required,
+            vesting,
+            retro,
+        ),
+        ExecuteMsg::AllocationBallot {
+            round_id,
+            allocations,
+        } => execute::allocation_ballot(deps, info, round_id, allocations),
+        ExecuteMsg::UpdateReviewers { add, remove } => {
+            execute::update_reviewers(deps, env, info, add, remove)
+        }
+        ExecuteMsg::UpdateReviewerGroup { group } => {
+            execute::update_reviewer_group(deps, info, group)
+        }
+        ExecuteMsg::Pause {} => execute::set_paused(deps, info, true),
+        ExecuteMsg::Unpause {} => execute::set_paused(deps, info, false),
+        ExecuteMsg::UpdateGuardian { guardian } => {
+            execute::update_guardian(deps, info, guardian)
+        }
+        ExecuteMsg::ProposeParameterChange {
+            proposal_id,
+            change,
+        } => execute::propose_parameter_change(deps, env, proposal_id, change),
+        ExecuteMsg::Propose {
+            proposal_id,
+            fund_address,
+            msgs,
+        } => execute::propose(deps, env, proposal_id, fund_address, msgs),
+        ExecuteMsg::ExecuteProposal { proposal_id } => {
+            execute::execute_proposal(deps, proposal_id)
+        }
+        ExecuteMsg::UpdateFee { fee } => execute::update_fee(deps, env, info, fee),
+        ExecuteMsg::WithdrawFees {} => execute::withdraw_fees(deps, info),
+        ExecuteMsg::WithdrawLeftover {
+            round_id,
+            recipient,
+        } => execute::withdraw_leftover(deps, env, info, round_id, recipient),
+        ExecuteMsg::RefundContribution { proposal_id } => {
+            execute::refund_contribution(deps, info, proposal_id)
+        }
+        ExecuteMsg::SetMilestones {
+            proposal_id,
+            milestones,
+        } => execute::set_milestones(deps, info, proposal_id, milestones),
+        ExecuteMsg::ApproveMilestone {
+            proposal_id,
+            milestone,
+            approved,
+        } => execute::approve_milestone(deps, info, proposal_id, milestone, approved),
+        ExecuteMsg::UpdateMilestoneThreshold { threshold } => {
+            execute::update_milestone_threshold(deps, env, info, threshold)
+        }
+        ExecuteMsg::UpdateUnitPrice { unit_price } => {
+            execute::update_unit_price(deps, env, info, unit_price)
+        }
+        ExecuteMsg::ClaimMilestone { proposal_id } => {
+            execute::claim_milestone(deps, info, proposal_id)
+        }
+        ExecuteMsg::CloseEscrow { proposal_id } => execute::close_escrow(deps, info, proposal_id),
+        ExecuteMsg::ClaimVested { proposal_id } => {
+            execute::claim_vested(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::ClawBack { proposal_id } => execute::claw_back(deps, env, info, proposal_id),
+        ExecuteMsg::Delegate { to } => execute::delegate(deps, info, to),
+        ExecuteMsg::Undelegate {} => execute::undelegate(deps, info),
+        ExecuteMsg::UpdateConfig {
+            ballot_mode,
+            voter_eligibility,
+            voting_mode,
+            ranking,
+        } => execute::update_config(
+            deps,
+            env,
+            info,
+            ballot_mode,
+            voter_eligibility,
+            voting_mode,
+            ranking,
+        ),
+        ExecuteMsg::UpdateAllowlist { add, remove } => {
+            execute::update_allowlist(deps, info, add, remove)
+        }
+        ExecuteMsg::SetTrustWeights { weights, default } => {
+            execute::set_trust_weights(deps, info, weights, default)
+        }
+        ExecuteMsg::ProposeFunding {
+            proposal_id,
+            beneficiary,
+            requested,
+        } => execute::propose_funding(deps, env, proposal_id, beneficiary, requested),
+        ExecuteMsg::FundConvictionPool {} => execute::fund_conviction_pool(deps, info),
+        ExecuteMsg::UpdateConviction { proposal_id } => {
+            execute::update_conviction(deps, env, proposal_id)
+        }
+        ExecuteMsg::WithdrawStake { proposal_id } => {
+            execute::withdraw_stake(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::ApprovalBallot { round_id, approved } => {
+            execute::submit_ballot(deps, info, round_id, Ballot::Approval { approved })
+        }
+        ExecuteMsg::RankedBallot { round_id, ranking } => {
+            execute::submit_ballot(deps, info, round_id, Ballot::Ranked { ranking })
+        }
+        ExecuteMsg::CommitVote {
+            proposal_id,
+            commitment,
+        } => execute::commit_vote(deps, info, proposal_id, commitment),
+        ExecuteMsg::RevealVote {
+            proposal_id,
+            votes,
+            yes,
+            salt,
+        } => execute::reveal_vote(deps, env, info, proposal_id, votes, yes, salt),
         //ExecuteMsg::Withdraw {} => execute::withdraw(deps, info),
     }
 }
 
+// platform fee on `amount` at the current rate
+fn platform_fee(state: &State, amount: Uint128) -> Uint128 {
+    match &state.fee {
+        Some(fee) => amount.multiply_ratio(fee.bps as u128, 10_000u128),
+        None => Uint128::zero(),
+    }
+}
+
+fn book_fee(storage: &mut dyn Storage, fee: Uint128) -> StdResult<()> {
+    if !fee.is_zero() {
+        let treasury = TREASURY.may_load(storage)?.unwrap_or_default() + fee;
+        TREASURY.save(storage, &treasury)?;
+    }
+    Ok(())
+}
+
+// books the platform fee on `amount` into the treasury and returns it
+fn collect_fee(storage: &mut dyn Storage, state: &State, amount: Uint128) -> StdResult<Uint128> {
+    let fee = platform_fee(state, amount);
+    book_fee(storage, fee)?;
+    Ok(fee)
+}
+
+// takes the platform fee off every match and returns the fees in grant order
+fn deduct_fees(state: &State, grants: &mut [(Uint128, CalculatedGrant)]) -> Vec<Uint128> {
+    grants
+        .iter_mut()
+        .map(|(_, grant)| {
+            let fee = platform_fee(state, Uint128::from(grant.grant));
+            grant.grant -= fee.u128();
+            fee
+        })
+        .collect()
+}
+
+// status names as serialized, for event attributes
+fn status_attr(status: &ProposalStatus) -> &'static str {
+    match status {
+        ProposalStatus::PreQual => "pre_qual",
+        ProposalStatus::InProgress => "in_progress",
+        ProposalStatus::Reviewed => "reviewed",
+        ProposalStatus::Passed => "passed",
+        ProposalStatus::Rejected => "rejected",
+        ProposalStatus::Revealing => "revealing",
+        ProposalStatus::Executed => "executed",
+    }
+}
+
+fn milestone_status_attr(status: &MilestoneStatus) -> &'static str {
+    match status {
+        MilestoneStatus::Pending => "pending",
+        MilestoneStatus::Approved => "approved",
+        MilestoneStatus::Rejected => "rejected",
+        MilestoneStatus::Claimed => "claimed",
+    }
+}
+
+fn join_ids(ids: &[Uint128]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// the owner, or the contract itself executing a passed parameter change
+fn is_governor(state: &State, env: &Env, sender: &Addr) -> bool {
+    *sender == state.owner || *sender == env.contract.address
+}
+
+// messages accepted while the contract is paused, everything else is rejected
+// so funds can only leave through the withdrawal and refund paths
+fn allowed_while_paused(msg: &ExecuteMsg) -> bool {
+    matches!(
+        msg,
+        ExecuteMsg::Pause {}
+            | ExecuteMsg::Unpause {}
+            | ExecuteMsg::UpdateGuardian { .. }
+            | ExecuteMsg::WithdrawStake { .. }
+            | ExecuteMsg::WithdrawFees {}
+            | ExecuteMsg::WithdrawLeftover { .. }
+            | ExecuteMsg::RefundContribution { .. }
+            | ExecuteMsg::Undelegate { .. }
+    )
+}
+
 pub mod execute {
-    use crate::state::Proposal;
+    use crate::quadratic::QuadraticFundingAlgorithm;
 
     use super::*;
 
     pub fn vote(
-        deps: DepsMut,
+        mut deps: DepsMut,
+        env: Env,
         info: MessageInfo,
         proposal_id: Uint128,
         yes_vote: bool,
@@ -80,264 +386,1035 @@ pub mod execute {
 
         let state = STATE.load(deps.storage)?;
 
-        if let Some(mut proposal) = PROPOSALS.may_load(deps.storage, key_proposal_id)? {
-            if yes_vote {
-                proposal.yes_votes += Uint128::from(1u128);
-            } else {
-                proposal.no_votes += Uint128::from(1u128);
-            }
-
-            let mut balance_member = BALANCES
-                .may_load(deps.storage, key_address)?
-                .unwrap_or_default();
-
-            let voter = VOTERS.may_load(deps.storage, key_address)?;
-
-            match voter {
-                Some(mut voter) => {
-                    let stake_amount =
-                        match voter.vote_count.iter().position(|&x| x.0 == proposal_id) {
-                            Some(index) => {
-                                println!("Go to here");
-                                let next_vote = voter.vote_count.get(index).unwrap().1 + 1;
+        if state.ballot_mode != BallotMode::Open {
+            return Err(ContractError::CommitRevealRequired {});
+        }
+        if !is_eligible(deps.as_ref(), &state, &info.sender)? {
+            return Err(ContractError::VoterNotEligible {});
+        }
+        // conviction accrues over time instead of being tallied per vote
+        if let VotingMode::Conviction { decay, threshold } = state.voting_mode {
+            return conviction_vote(
+                deps,
+                env,
+                info,
+                &state,
+                proposal_id,
+                yes_vote,
+                decay,
+                threshold,
+            );
+        }
 
-                                let insert_vote = (proposal_id, next_vote);
+        let mut proposal = match PROPOSALS.may_load(deps.storage, key_proposal_id)? {
+            // reviews and distributions decide on the tally as it was when the vote ended
+            Some(proposal) if proposal.status != ProposalStatus::InProgress => {
+                return Err(ContractError::ProposalNotInProgress {})
+            }
+            Some(mut proposal) => {
+                // voting directly overrides whatever a delegate cast for the sender,
+                // the stake it took goes back to their credits
+                let key_delegated = (key_proposal_id.as_slice(), key_address);
+                if let Some(delegated) = DELEGATED_VOTES.may_load(deps.storage, key_delegated)? {
+                    if delegated.yes {
+                        proposal.yes_votes -= Uint128::from(1u128);
+                    } else {
+                        proposal.no_votes -= Uint128::from(1u128);
+                    }
+                    if !delegated.stake.is_zero() {
+                        let mut balance = BALANCES.load(deps.storage, key_address)?;
+                        unlock_tokens(&mut balance, proposal_id, delegated.stake, !delegated.yes)?;
+                        BALANCES.save(deps.storage, key_address, &balance)?;
+                        let credits = DELEGATION_CREDITS
+                            .may_load(deps.storage, key_address)?
+                            .unwrap_or_default();
+                        DELEGATION_CREDITS.save(
+                            deps.storage,
+                            key_address,
+                            &(credits + delegated.stake),
+                        )?;
+                    }
+                    DELEGATED_VOTES.remove(deps.storage, key_delegated);
+                }
+                proposal
+            }
+            // voting power is read at the height the proposal was created at,
+            // which must not be left to whoever votes first
+            None if matches!(state.voting_mode, VotingMode::Snapshot { .. }) => {
+                return Err(ContractError::ProposalNotFound {})
+            }
+            // the first vote opens the proposal
+            None => Proposal {
+                fund_address: state.owner.clone().into_string(),
+                status: ProposalStatus::InProgress,
+                start_height: env.block.height,
+                ..Default::default()
+            },
+        };
 
-                                voter.vote_count.insert(index, insert_vote);
+        let mut voter = VOTERS
+            .may_load(deps.storage, key_address)?
+            .unwrap_or(Voter {
+                vote_count: vec![],
+                against: vec![],
+            });
+        let index = match voter.vote_count.iter().position(|&x| x.0 == proposal_id) {
+            Some(index) => index,
+            None => {
+                voter.vote_count.push((proposal_id, 0));
+                voter.vote_count.len() - 1
+            }
+        };
+        let previous = voter.vote_count[index].1;
+        voter.vote_count[index].1 += 1;
+        if !yes_vote {
+            count_against(&mut voter, proposal_id, 1);
+        }
 
-                                next_vote.checked_pow(2)
-                            }
-                            None => return Err(ContractError::ProposalNotFound {}),
-                        };
-                    let amount = MIN_STAKE_AMOUNT
-                        .checked_mul(stake_amount.unwrap() as u128)
-                        .unwrap_or_default();
+        // weight added to the tally and tokens locked for the vote
+        let mut excess = Uint128::zero();
+        let (weight, stake) = match &state.voting_mode {
+            VotingMode::Quadratic => {
+                let amount = quadratic_cost(&state, previous, 1)?;
+                excess =
                     validate_sent_sufficient_coin(&info.funds, Some(coin(amount, &state.denom)))?;
-
-                    VOTERS.save(deps.storage, key_address, &voter)?;
+                (Uint128::from(1u128), Uint128::from(amount))
+            }
+            VotingMode::Linear => {
+                if let Some(other) = info.funds.iter().find(|coin| coin.denom != state.denom) {
+                    return Err(ContractError::WrongFundCoin {
+                        expected: state.denom.clone(),
+                        got: other.denom.clone(),
+                    });
                 }
-                None => {
-                    let count = 1;
-                    let voter = Voter {
-                        vote_count: vec![(proposal_id, count)],
-                    };
-
-                    validate_sent_sufficient_coin(
-                        &info.funds,
-                        Some(coin(MIN_STAKE_AMOUNT, &state.denom)),
-                    )?;
-
-                    VOTERS.save(deps.storage, key_address, &voter)?;
+                let stake = info
+                    .funds
+                    .iter()
+                    .find(|coin| coin.denom.eq(&state.denom))
+                    .map(|coin| coin.amount)
+                    .unwrap_or_default();
+                if stake.is_zero() {
+                    return Err(ContractError::InsufficientFundsSent {});
                 }
+                (stake, stake)
             }
-
-            let funds = info
-                .funds
-                .iter()
-                .find(|coin| coin.denom.eq(&state.denom))
-                .unwrap();
-
-            balance_member.token_balance += funds.amount;
-
-            BALANCES.save(deps.storage, key_address, &balance_member)?;
-            PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
-        } else {
-            // First time
-            //voter 1 -> proposal 1
-            // voter 2 -> proposal 1
-
-            // voter 1 -> proposal 1
-            // voter 2 -> proposal 2
-
-            let mut yes = Uint128::from(0u128);
-            let mut no = Uint128::from(0u128);
-            if yes_vote {
-                yes = Uint128::from(1u128);
-            } else {
-                no = Uint128::from(1u128);
+            VotingMode::OnePersonOneVote => {
+                if !ALLOWLIST.has(deps.storage, key_address) {
+                    return Err(ContractError::VoterNotEligible {});
+                }
+                if previous > 0 {
+                    return Err(ContractError::AlreadyVoted {});
+                }
+                if !info.funds.is_empty() {
+                    return Err(ContractError::FundsNotAccepted {});
+                }
+                (Uint128::from(1u128), Uint128::zero())
             }
+            VotingMode::Snapshot { contract } => {
+                if previous > 0 {
+                    return Err(ContractError::AlreadyVoted {});
+                }
+                if !info.funds.is_empty() {
+                    return Err(ContractError::FundsNotAccepted {});
+                }
+                let res: SnapshotBalanceResponse = deps.querier.query_wasm_smart(
+                    contract,
+                    &SnapshotQueryMsg::BalanceAtHeight {
+                        address: info.sender.to_string(),
+                        height: Some(proposal.start_height),
+                    },
+                )?;
+                if res.balance.is_zero() {
+                    return Err(ContractError::NoVotingPower {});
+                }
+                (res.balance, Uint128::zero())
+            }
+            VotingMode::Conviction { .. } => return Err(ContractError::UnsupportedVotingMode {}),
+        };
 
-            let new_proposal = Proposal {
-                fund_address: state.owner.clone().into_string(),
-                status: ProposalStatus::InProgress,
-                yes_votes: yes,
-                no_votes: no,
-                voters: Vec::new(),
-            };
-
+        if yes_vote {
+            proposal.yes_votes += weight;
+        } else {
+            proposal.no_votes += weight;
+        }
+        // the fee is taken before the payment counts as a contribution
+        let fee = collect_fee(deps.storage, &state, stake)?;
+        let stake = stake - fee;
+        if !stake.is_zero() {
             let mut balance_member = BALANCES
                 .may_load(deps.storage, key_address)?
                 .unwrap_or_default();
-
-            validate_sent_sufficient_coin(&info.funds, Some(coin(MIN_STAKE_AMOUNT, &state.denom)))?;
-
-            let funds = info
-                .funds
-                .iter()
-                .find(|coin| coin.denom.eq(&state.denom))
-                .unwrap();
-
-            balance_member.token_balance += funds.amount;
-
-            let count = 1;
-            let voter = Voter {
-                vote_count: vec![(proposal_id, count)],
-            };
+            balance_member.token_balance += stake;
+            lock_tokens(&mut balance_member, proposal_id, stake);
+            if !yes_vote {
+                lock_against(&mut balance_member, proposal_id, stake);
+            }
             BALANCES.save(deps.storage, key_address, &balance_member)?;
-
-            PROPOSALS.save(deps.storage, key_proposal_id, &new_proposal)?;
-            VOTERS.save(deps.storage, key_address, &voter)?;
         }
-        STATE.save(deps.storage, &state)?;
+        if !proposal.voters.contains(&info.sender) {
+            proposal.voters.push(info.sender.clone());
+        }
+        // delegators count as one vote, which only fits the unweighted modes
+        if matches!(
+            state.voting_mode,
+            VotingMode::Quadratic | VotingMode::OnePersonOneVote
+        ) {
+            cast_delegated_votes(
+                deps.branch(),
+                &state,
+                &mut proposal,
+                proposal_id,
+                &info.sender,
+                yes_vote,
+            )?;
+        }
 
-        Ok(Response::new().add_attribute("action", "vote"))
+        VOTERS.save(deps.storage, key_address, &voter)?;
+        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_messages(refund_excess(&info.sender, excess, &state.denom))
+            .add_event(
+                Event::new("vote")
+                    .add_attribute("voter", info.sender)
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("yes_vote", yes_vote.to_string())
+                    .add_attribute("votes", weight)
+                    .add_attribute("amount", stake)
+                    .add_attribute("fee", fee)
+                    .add_attribute("refund", excess)
+                    .add_attribute("yes_votes", proposal.yes_votes)
+                    .add_attribute("no_votes", proposal.no_votes),
+            )
+            .add_attribute("action", "vote"))
     }
 
-    //reviewer 1 -> review ->proposal 1 passed
-    // reviewer 2 ->review ->  proposal 1 passed
-
-    // reviewer 1 -> -> proposal 1 passed
-    // reviewer 2 -> proposal 2 passed
-    pub fn review(
+    // counts one vote for every delegator of `delegate` that has neither voted
+    // on the proposal itself nor been counted through the delegate already. in
+    // quadratic mode the vote is paid from the delegator's credits and skipped
+    // when they run out
+    fn cast_delegated_votes(
         deps: DepsMut,
-        info: MessageInfo,
+        state: &State,
+        proposal: &mut Proposal,
         proposal_id: Uint128,
-        approved: bool,
-    ) -> Result<Response, ContractError> {
+        delegate: &Addr,
+        yes_vote: bool,
+    ) -> Result<(), ContractError> {
+        if proposal.status != ProposalStatus::InProgress {
+            return Err(ContractError::ProposalNotInProgress {});
+        }
         let key_proposal_id = &proposal_id.to_be_bytes();
-
-        let key_address = info.sender.as_str().as_bytes();
-
-        let state = STATE.load(deps.storage)?;
-
-        if let Some(mut proposal) = PROPOSALS.may_load(deps.storage, key_proposal_id)? {
-            
-            if proposal.status != ProposalStatus::Reviewed {
-                return Err(ContractError::ProposalNotInReview{});
-            
+        let delegators = DELEGATORS
+            .may_load(deps.storage, delegate.as_str().as_bytes())?
+            .unwrap_or_default();
+        for delegator in delegators {
+            let key_delegator = delegator.as_str().as_bytes();
+            let key_delegated = (key_proposal_id.as_slice(), key_delegator);
+            if DELEGATED_VOTES.has(deps.storage, key_delegated) {
+                continue;
             }
-            if approved {
+            let voted_directly = VOTERS
+                .may_load(deps.storage, key_delegator)?
+                .map(|v| v.vote_count.iter().any(|(id, _)| *id == proposal_id))
+                .unwrap_or(false);
+            if voted_directly {
+                continue;
+            }
+            // eligibility may have been revoked since the delegation
+            if !is_eligible(deps.as_ref(), state, &delegator)? {
+                continue;
+            }
+            // one person one vote only counts allowlisted people, delegated or not
+            if state.voting_mode == VotingMode::OnePersonOneVote
+                && !ALLOWLIST.has(deps.storage, key_delegator)
+            {
+                continue;
+            }
+            let mut stake = Uint128::zero();
+            if state.voting_mode == VotingMode::Quadratic {
+                let cost = Uint128::from(quadratic_cost(state, 0, 1)?);
+                let credits = DELEGATION_CREDITS
+                    .may_load(deps.storage, key_delegator)?
+                    .unwrap_or_default();
+                if credits < cost {
+                    continue;
+                }
+                DELEGATION_CREDITS.save(deps.storage, key_delegator, &(credits - cost))?;
+                stake = cost - collect_fee(deps.storage, state, cost)?;
+                let mut balance = BALANCES
+                    .may_load(deps.storage, key_delegator)?
+                    .unwrap_or_default();
+                balance.token_balance += stake;
+                lock_tokens(&mut balance, proposal_id, stake);
+                if !yes_vote {
+                    lock_against(&mut balance, proposal_id, stake);
+                }
+                BALANCES.save(deps.storage, key_delegator, &balance)?;
+                if !proposal.voters.contains(&delegator) {
+                    proposal.voters.push(delegator.clone());
+                }
+            }
+            if yes_vote {
                 proposal.yes_votes += Uint128::from(1u128);
             } else {
                 proposal.no_votes += Uint128::from(1u128);
             }
+            let delegated = DelegatedVote {
+                yes: yes_vote,
+                stake,
+            };
+            DELEGATED_VOTES.save(deps.storage, key_delegated, &delegated)?;
+        }
+        Ok(())
+    }
 
-            let mut balance_member = BALANCES
-                .may_load(deps.storage, key_address)?
-                .unwrap_or_default();
-
-            let voter = VOTERS.may_load(deps.storage, key_address)?;
-
-            match voter {
-                Some(mut voter) => {
-                    let stake_amount =
-                        match voter.vote_count.iter().position(|&x| x.0 == proposal_id) {
-                            Some(index) => {
-                                println!("Go to here");
-                                let next_vote = voter.vote_count.get(index).unwrap().1 + 1;
-
-                                let insert_vote = (proposal_id, next_vote);
-
-                                voter.vote_count.insert(index, insert_vote);
-
-                                next_vote.checked_pow(2)
-                            }
-                            None => return Err(ContractError::ProposalNotFound {}),
-                        };
-                    let amount = MIN_STAKE_AMOUNT
-                        .checked_mul(stake_amount.unwrap() as u128)
-                        .unwrap_or_default();
-                    validate_sent_sufficient_coin(&info.funds, Some(coin(amount, &state.denom)))?;
-
-                    VOTERS.save(deps.storage, key_address, &voter)?;
-                }
-                None => {
-                    let count = 1;
-                    let voter = Voter {
-                        vote_count: vec![(proposal_id, count)],
-                    };
-
-                    validate_sent_sufficient_coin(
-                        &info.funds,
-                        Some(coin(MIN_STAKE_AMOUNT, &state.denom)),
-                    )?;
-
-                    VOTERS.save(deps.storage, key_address, &voter)?;
-                }
+    pub fn delegate(
+        deps: DepsMut,
+        info: MessageInfo,
+        to: String,
+    ) -> Result<Response, ContractError> {
+        let to = deps.api.addr_validate(&to)?;
+        if to == info.sender {
+            return Err(ContractError::SelfDelegation {});
+        }
+        // checked again for every vote the delegate casts on the sender's behalf
+        let state = STATE.load(deps.storage)?;
+        if !is_eligible(deps.as_ref(), &state, &info.sender)? {
+            return Err(ContractError::VoterNotEligible {});
+        }
+        let key_address = info.sender.as_str().as_bytes();
+        let deposit = match info.funds.as_slice() {
+            [] => Uint128::zero(),
+            [coin] if coin.denom == state.denom => coin.amount,
+            [coin, ..] => {
+                return Err(ContractError::WrongFundCoin {
+                    expected: state.denom,
+                    got: coin.denom.clone(),
+                })
             }
+        };
+        let credits = DELEGATION_CREDITS
+            .may_load(deps.storage, key_address)?
+            .unwrap_or_default()
+            + deposit;
+        DELEGATION_CREDITS.save(deps.storage, key_address, &credits)?;
+
+        if let Some(previous) = DELEGATIONS.may_load(deps.storage, key_address)? {
+            remove_delegator(deps.storage, &previous, &info.sender)?;
+        }
+        DELEGATIONS.save(deps.storage, key_address, &to)?;
+        let mut delegators = DELEGATORS
+            .may_load(deps.storage, to.as_str().as_bytes())?
+            .unwrap_or_default();
+        delegators.push(info.sender.clone());
+        DELEGATORS.save(deps.storage, to.as_str().as_bytes(), &delegators)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("delegate")
+                    .add_attribute("delegator", info.sender.clone())
+                    .add_attribute("delegate", to.clone())
+                    .add_attribute("credits", credits),
+            )
+            .add_attribute("action", "delegate")
+            .add_attribute("delegator", info.sender)
+            .add_attribute("delegate", to))
+    }
 
-            let funds = info
-                .funds
-                .iter()
-                .find(|coin| coin.denom.eq(&state.denom))
-                .unwrap();
-
-            balance_member.token_balance += funds.amount;
+    // votes already cast by the delegate stay counted, unused credits are returned
+    pub fn undelegate(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        let key_address = info.sender.as_str().as_bytes();
+        let state = STATE.load(deps.storage)?;
 
-            BALANCES.save(deps.storage, key_address, &balance_member)?;
-            PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
-        } else {
-            return Err(ContractError::ProposalNotFound {});
+        let previous = DELEGATIONS
+            .may_load(deps.storage, key_address)?
+            .ok_or(ContractError::NotDelegated {})?;
+        remove_delegator(deps.storage, &previous, &info.sender)?;
+        DELEGATIONS.remove(deps.storage, key_address);
+        let credits = DELEGATION_CREDITS
+            .may_load(deps.storage, key_address)?
+            .unwrap_or_default();
+        DELEGATION_CREDITS.remove(deps.storage, key_address);
+
+        let mut res = Response::new();
+        if !credits.is_zero() {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(credits.u128(), &state.denom)],
+            });
         }
-        STATE.save(deps.storage, &state)?;
+        Ok(res
+            .add_event(
+                Event::new("undelegate")
+                    .add_attribute("delegator", info.sender.clone())
+                    .add_attribute("delegate", previous.clone())
+                    .add_attribute("refund", credits),
+            )
+            .add_attribute("action", "undelegate")
+            .add_attribute("delegator", info.sender)
+            .add_attribute("delegate", previous))
+    }
 
-        Ok(Response::new().add_attribute("action", "review"))
+    fn remove_delegator(
+        storage: &mut dyn Storage,
+        delegate: &Addr,
+        delegator: &Addr,
+    ) -> StdResult<()> {
+        let key_delegate = delegate.as_str().as_bytes();
+        let mut delegators = DELEGATORS
+            .may_load(storage, key_delegate)?
+            .unwrap_or_default();
+        delegators.retain(|d| d != delegator);
+        DELEGATORS.save(storage, key_delegate, &delegators)
     }
 
-    pub fn end_vote(
+    #[allow(clippy::too_many_arguments)]
+    fn conviction_vote(
         deps: DepsMut,
+        env: Env,
         info: MessageInfo,
+        state: &State,
         proposal_id: Uint128,
+        yes_vote: bool,
+        decay: Decimal,
+        threshold: Decimal,
     ) -> Result<Response, ContractError> {
         let key_proposal_id = &proposal_id.to_be_bytes();
-        let state = STATE.load(deps.storage)?;
+        let key_address = info.sender.as_str().as_bytes();
 
-        if info.sender != state.owner {
-            return Err(ContractError::Unauthorized {});
+        if !yes_vote {
+            return Err(ContractError::ConvictionSupportOnly {});
         }
-        match PROPOSALS.may_load(deps.storage, key_proposal_id)? {
-            Some(mut proposal) => {
-                if proposal.status != ProposalStatus::InProgress {
-                    return Err(ContractError::ProposalNotInProgress {});
-                }
+        let mut proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        let mut conviction = CONVICTIONS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::NotConvictionProposal {})?;
+        if proposal.status != ProposalStatus::InProgress {
+            return Err(ContractError::ProposalNotInProgress {});
+        }
+        // coins of other denoms would be kept without counting
+        if let Some(other) = info.funds.iter().find(|coin| coin.denom != state.denom) {
+            return Err(ContractError::WrongFundCoin {
+                expected: state.denom.clone(),
+                got: other.denom.clone(),
+            });
+        }
+        let stake = info
+            .funds
+            .iter()
+            .find(|coin| coin.denom.eq(&state.denom))
+            .map(|coin| coin.amount)
+            .unwrap_or_default();
+        if stake.is_zero() {
+            return Err(ContractError::InsufficientFundsSent {});
+        }
+        let fee = collect_fee(deps.storage, state, stake)?;
+        let stake = stake - fee;
+
+        // conviction so far is accrued at the old stake
+        conviction.accrue(env.block.height, decay);
+        conviction.staked += stake;
+        proposal.yes_votes += stake;
+        if !proposal.voters.contains(&info.sender) {
+            proposal.voters.push(info.sender.clone());
+        }
+        let mut balance_member = BALANCES
+            .may_load(deps.storage, key_address)?
+            .unwrap_or_default();
+        balance_member.token_balance += stake;
+        BALANCES.save(deps.storage, key_address, &balance_member)?;
+        let key_stake = (key_proposal_id.as_slice(), key_address);
+        let staked = CONVICTION_STAKES
+            .may_load(deps.storage, key_stake)?
+            .unwrap_or_default();
+        CONVICTION_STAKES.save(deps.storage, key_stake, &(staked + stake))?;
+
+        let payout =
+            pass_on_conviction(deps.storage, state, &mut proposal, &conviction, threshold)?;
+        CONVICTIONS.save(deps.storage, key_proposal_id, &conviction)?;
+        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("vote")
+                    .add_attribute("voter", info.sender)
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("amount", stake)
+                    .add_attribute("fee", fee)
+                    .add_attribute("staked", conviction.staked)
+                    .add_attribute("conviction", conviction.conviction)
+                    .add_attribute("status", status_attr(&proposal.status)),
+            )
+            .add_messages(payout)
+            .add_attribute("action", "vote")
+            .add_attribute("conviction", conviction.conviction))
+    }
 
-                if proposal.yes_votes > proposal.no_votes {
-                    proposal.status = ProposalStatus::Reviewed;
-                } else {
-                    proposal.status = ProposalStatus::Rejected;
-                }
-                proposal.yes_votes = Uint128::from(0u128);
-                proposal.no_votes = Uint128::from(0u128);
-                PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+    // passes the proposal and pays the request from the pool once conviction is high enough
+    fn pass_on_conviction(
+        storage: &mut dyn Storage,
+        state: &State,
+        proposal: &mut Proposal,
+        conviction: &Conviction,
+        threshold: Decimal,
+    ) -> StdResult<Option<BankMsg>> {
+        let pool = CONVICTION_POOL.may_load(storage)?.unwrap_or_default();
+        match required_conviction(threshold, conviction.requested, pool) {
+            Some(required) if conviction.conviction >= required => {
+                proposal.status = ProposalStatus::Passed;
+                CONVICTION_POOL.save(storage, &(pool - conviction.requested))?;
+                Ok(Some(BankMsg::Send {
+                    to_address: proposal.fund_address.clone(),
+                    amount: vec![coin(conviction.requested.u128(), &state.denom)],
+                }))
             }
-            None => return Err(ContractError::ProposalNotFound {}),
-        };
-
-        Ok(Response::new().add_attribute("action", "end_vote"))
+            _ => Ok(None),
+        }
     }
 
-    pub fn end_review(
+    pub fn propose_funding(
         deps: DepsMut,
-        info: MessageInfo,
+        env: Env,
         proposal_id: Uint128,
+        beneficiary: String,
+        requested: Uint128,
     ) -> Result<Response, ContractError> {
         let key_proposal_id = &proposal_id.to_be_bytes();
         let state = STATE.load(deps.storage)?;
 
-        if info.sender != state.owner {
-            return Err(ContractError::Unauthorized {});
+        if !matches!(state.voting_mode, VotingMode::Conviction { .. }) {
+            return Err(ContractError::NotConvictionProposal {});
         }
-        match PROPOSALS.may_load(deps.storage, key_proposal_id)? {
-            Some(mut proposal) => {
-                if proposal.status != ProposalStatus::Reviewed {
-                    return Err(ContractError::ProposalNotInReview {});
-                }
+        if PROPOSALS.has(deps.storage, key_proposal_id) {
+            return Err(ContractError::ProposalAlreadyExists {});
+        }
+        if requested.is_zero() {
+            return Err(ContractError::InvalidConviction {});
+        }
+        let beneficiary = deps.api.addr_validate(&beneficiary)?;
 
-                if proposal.yes_votes > proposal.no_votes {
-                    proposal.status = ProposalStatus::Passed;
+        PROPOSALS.save(
+            deps.storage,
+            key_proposal_id,
+            &Proposal {
+                fund_address: beneficiary.to_string(),
+                status: ProposalStatus::InProgress,
+                start_height: env.block.height,
+                ..Default::default()
+            },
+        )?;
+        CONVICTIONS.save(
+            deps.storage,
+            key_proposal_id,
+            &Conviction {
+                requested,
+                staked: Uint128::zero(),
+                conviction: Uint128::zero(),
+                last_height: env.block.height,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("propose_funding")
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("beneficiary", beneficiary)
+                    .add_attribute("requested", requested),
+            )
+            .add_attribute("action", "propose_funding")
+            .add_attribute("proposal_id", proposal_id))
+    }
+
+    pub fn fund_conviction_pool(
+        deps: DepsMut,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        let amount = extract_budget_coin(&info.funds, &state.denom)?.amount;
+
+        let pool = CONVICTION_POOL.may_load(deps.storage)?.unwrap_or_default() + amount;
+        CONVICTION_POOL.save(deps.storage, &pool)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("fund_conviction_pool")
+                    .add_attribute("sender", info.sender)
+                    .add_attribute("amount", amount)
+                    .add_attribute("pool", pool),
+            )
+            .add_attribute("action", "fund_conviction_pool")
+            .add_attribute("pool", pool))
+    }
+
+    // anyone can trigger the check, conviction grows without new votes
+    pub fn update_conviction(
+        deps: DepsMut,
+        env: Env,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let (decay, threshold) = match state.voting_mode {
+            VotingMode::Conviction { decay, threshold } => (decay, threshold),
+            _ => return Err(ContractError::NotConvictionProposal {}),
+        };
+        let mut proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        let mut conviction = CONVICTIONS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::NotConvictionProposal {})?;
+        if proposal.status != ProposalStatus::InProgress {
+            return Err(ContractError::ProposalNotInProgress {});
+        }
+
+        conviction.accrue(env.block.height, decay);
+        let payout =
+            pass_on_conviction(deps.storage, &state, &mut proposal, &conviction, threshold)?;
+        CONVICTIONS.save(deps.storage, key_proposal_id, &conviction)?;
+        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("update_conviction")
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("conviction", conviction.conviction)
+                    .add_attribute("status", status_attr(&proposal.status)),
+            )
+            .add_messages(payout)
+            .add_attribute("action", "update_conviction")
+            .add_attribute("conviction", conviction.conviction))
+    }
+
+    // returns the sender's stake on a conviction proposal, passed or not
+    pub fn withdraw_stake(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let key_address = info.sender.as_str().as_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let mut conviction = CONVICTIONS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::NotConvictionProposal {})?;
+        let mut proposal = PROPOSALS.load(deps.storage, key_proposal_id)?;
+        let mut balance_member = BALANCES
+            .may_load(deps.storage, key_address)?
+            .unwrap_or_default();
+        let key_stake = (key_proposal_id.as_slice(), key_address);
+        let amount = CONVICTION_STAKES
+            .may_load(deps.storage, key_stake)?
+            .ok_or(ContractError::NothingToClaim {})?;
+        CONVICTION_STAKES.remove(deps.storage, key_stake);
+        balance_member.token_balance = balance_member
+            .token_balance
+            .checked_sub(amount)
+            .map_err(|_| ContractError::OverflowError {})?;
+
+        if let VotingMode::Conviction { decay, .. } = state.voting_mode {
+            conviction.accrue(env.block.height, decay);
+        }
+        conviction.staked = conviction
+            .staked
+            .checked_sub(amount)
+            .map_err(|_| ContractError::OverflowError {})?;
+        proposal.yes_votes = proposal
+            .yes_votes
+            .checked_sub(amount)
+            .map_err(|_| ContractError::OverflowError {})?;
+
+        BALANCES.save(deps.storage, key_address, &balance_member)?;
+        CONVICTIONS.save(deps.storage, key_proposal_id, &conviction)?;
+        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("withdraw_stake")
+                    .add_attribute("voter", info.sender.clone())
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("amount", amount)
+                    .add_attribute("staked", conviction.staked),
+            )
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(amount.u128(), &state.denom)],
+            })
+            .add_attribute("action", "withdraw_stake")
+            .add_attribute("amount", amount))
+    }
+
+    pub fn submit_ballot(
+        deps: DepsMut,
+        info: MessageInfo,
+        round_id: Uint128,
+        ballot: Ballot,
+    ) -> Result<Response, ContractError> {
+        let key_round_id = &round_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let ranking = state
+            .ranking
+            .as_ref()
+            .ok_or(ContractError::RankingDisabled {})?;
+        let proposals = match (&ranking.method, &ballot) {
+            (RankingMethod::Approval, Ballot::Approval { approved }) => approved,
+            (RankingMethod::Borda | RankingMethod::InstantRunoff, Ballot::Ranked { ranking }) => {
+                ranking
+            }
+            _ => return Err(ContractError::WrongBallotType {}),
+        };
+        if !is_eligible(deps.as_ref(), &state, &info.sender)? {
+            return Err(ContractError::VoterNotEligible {});
+        }
+        let round = ROUNDS
+            .may_load(deps.storage, key_round_id)?
+            .ok_or(ContractError::RoundNotFound {})?;
+        if DISTRIBUTIONS.has(deps.storage, key_round_id) {
+            return Err(ContractError::RoundAlreadyDistributed {});
+        }
+        for (i, proposal_id) in proposals.iter().enumerate() {
+            if !round.proposals.contains(proposal_id) || proposals[..i].contains(proposal_id) {
+                return Err(ContractError::InvalidBallot {});
+            }
+        }
+
+        let event = Event::new("submit_ballot")
+            .add_attribute("voter", info.sender.clone())
+            .add_attribute("round_id", round_id)
+            .add_attribute("proposals", join_ids(proposals));
+        BALLOTS.save(
+            deps.storage,
+            (key_round_id.as_slice(), info.sender.as_str().as_bytes()),
+            &ballot,
+        )?;
+
+        Ok(Response::new()
+            .add_event(event)
+            .add_attribute("action", "submit_ballot")
+            .add_attribute("round_id", round_id))
+    }
+
+    pub fn allocation_ballot(
+        deps: DepsMut,
+        info: MessageInfo,
+        round_id: Uint128,
+        allocations: Vec<(Uint128, Uint128)>,
+    ) -> Result<Response, ContractError> {
+        let key_round_id = &round_id.to_be_bytes();
+
+        let round = ROUNDS
+            .may_load(deps.storage, key_round_id)?
+            .ok_or(ContractError::RoundNotFound {})?;
+        let retro = round
+            .retro
+            .as_ref()
+            .ok_or(ContractError::NotRetroRound {})?;
+        if !retro.badgeholders.contains(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if DISTRIBUTIONS.has(deps.storage, key_round_id) {
+            return Err(ContractError::RoundAlreadyDistributed {});
+        }
+        for (i, (proposal_id, _)) in allocations.iter().enumerate() {
+            if !round.proposals.contains(proposal_id)
+                || allocations[..i].iter().any(|(id, _)| id == proposal_id)
+            {
+                return Err(ContractError::InvalidBallot {});
+            }
+        }
+        let total: Uint128 = allocations.iter().map(|(_, amount)| amount).sum();
+        if total > round.budget {
+            return Err(ContractError::AllocationExceedsBudget {});
+        }
+
+        ALLOCATIONS.save(
+            deps.storage,
+            (key_round_id.as_slice(), info.sender.as_str().as_bytes()),
+            &allocations,
+        )?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("allocation_ballot")
+                    .add_attribute("badgeholder", info.sender)
+                    .add_attribute("round_id", round_id)
+                    .add_attribute("allocated", total),
+            )
+            .add_attribute("action", "allocation_ballot")
+            .add_attribute("round_id", round_id))
+    }
+
+    fn lock_tokens(balance: &mut BalanceVote, proposal_id: Uint128, amount: Uint128) {
+        match balance
+            .locked_tokens
+            .iter_mut()
+            .find(|(id, _)| *id == proposal_id)
+        {
+            Some(locked) => locked.1 += amount,
+            None => balance.locked_tokens.push((proposal_id, amount)),
+        }
+    }
+
+    // reverses lock_tokens, and lock_against for a no-vote
+    fn unlock_tokens(
+        balance: &mut BalanceVote,
+        proposal_id: Uint128,
+        amount: Uint128,
+        against: bool,
+    ) -> Result<(), ContractError> {
+        let overflow = |_| ContractError::OverflowError {};
+        balance.token_balance = balance
+            .token_balance
+            .checked_sub(amount)
+            .map_err(overflow)?;
+        let mut locked = vec![&mut balance.locked_tokens];
+        if against {
+            locked.push(&mut balance.locked_against);
+        }
+        for entries in locked {
+            let entry = entries
+                .iter_mut()
+                .find(|(id, _)| *id == proposal_id)
+                .ok_or(ContractError::OverflowError {})?;
+            entry.1 = entry.1.checked_sub(amount).map_err(overflow)?;
+            entries.retain(|(_, amount)| !amount.is_zero());
+        }
+        Ok(())
+    }
+
+    fn count_against(voter: &mut Voter, proposal_id: Uint128, votes: u64) {
+        match voter.against.iter_mut().find(|(id, _)| *id == proposal_id) {
+            Some(against) => against.1 += votes,
+            None => voter.against.push((proposal_id, votes)),
+        }
+    }
+
+    // part of the locked tokens paid for no-votes, see negative quadratic funding
+    fn lock_against(balance: &mut BalanceVote, proposal_id: Uint128, amount: Uint128) {
+        match balance
+            .locked_against
+            .iter_mut()
+            .find(|(id, _)| *id == proposal_id)
+        {
+            Some(locked) => locked.1 += amount,
+            None => balance.locked_against.push((proposal_id, amount)),
+        }
+    }
+
+    //reviewer 1 -> review ->proposal 1 passed
+    // reviewer 2 ->review ->  proposal 1 passed
+
+    // reviewer 1 -> -> proposal 1 passed
+    // reviewer 2 -> proposal 2 passed
+    pub fn review(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+        approved: bool,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+
+        let key_address = info.sender.as_str().as_bytes();
+
+        let state = STATE.load(deps.storage)?;
+
+        if let Some(group) = &state.reviewer_group {
+            return group_review(deps, info, proposal_id, approved, group);
+        }
+        let mut event = Event::new("review")
+            .add_attribute("reviewer", info.sender.clone())
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("approved", approved.to_string());
+        let refund;
+
+        if let Some(mut proposal) = PROPOSALS.may_load(deps.storage, key_proposal_id)? {
+            
+            if proposal.status != ProposalStatus::Reviewed {
+                return Err(ContractError::ProposalNotInReview{});
+            
+            }
+            if approved {
+                proposal.yes_votes += Uint128::from(1u128);
+            } else {
+                proposal.no_votes += Uint128::from(1u128);
+            }
+
+            let mut balance_member = BALANCES
+                .may_load(deps.storage, key_address)?
+                .unwrap_or_default();
+
+            let mut voter = VOTERS
+                .may_load(deps.storage, key_address)?
+                .unwrap_or(Voter {
+                    vote_count: vec![],
+                    against: vec![],
+                });
+            let previous = match voter.vote_count.iter().position(|&x| x.0 == proposal_id) {
+                Some(index) => {
+                    let previous = voter.vote_count[index].1;
+                    voter.vote_count[index] = (proposal_id, previous + 1);
+                    previous
+                }
+                None => {
+                    voter.vote_count.push((proposal_id, 1));
+                    0
+                }
+            };
+            // reviews are priced like votes, the n-th costs n^2 units
+            let amount = quadratic_cost(&state, previous, 1)?;
+            let excess =
+                validate_sent_sufficient_coin(&info.funds, Some(coin(amount, &state.denom)))?;
+            VOTERS.save(deps.storage, key_address, &voter)?;
+
+            // only the stake is credited, the excess goes back to the reviewer
+            balance_member.token_balance += Uint128::from(amount);
+            refund = refund_excess(&info.sender, excess, &state.denom);
+            event = event
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("refund", excess)
+                .add_attribute("yes_votes", proposal.yes_votes)
+                .add_attribute("no_votes", proposal.no_votes);
+
+            BALANCES.save(deps.storage, key_address, &balance_member)?;
+            PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+        } else {
+            return Err(ContractError::ProposalNotFound {});
+        }
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_messages(refund)
+            .add_event(event)
+            .add_attribute("action", "review"))
+    }
+
+    // committee review, each cw4 group member counts once with its weight
+    fn group_review(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+        approved: bool,
+        group: &Addr,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let key_address = info.sender.as_str().as_bytes();
+
+        let mut proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if proposal.status != ProposalStatus::Reviewed {
+            return Err(ContractError::ProposalNotInReview {});
+        }
+        if !info.funds.is_empty() {
+            return Err(ContractError::FundsNotAccepted {});
+        }
+        let member: MemberResponse = deps.querier.query_wasm_smart(
+            group,
+            &Cw4QueryMsg::Member {
+                addr: info.sender.to_string(),
+                at_height: Some(proposal.review_height),
+            },
+        )?;
+        let weight = member.weight.unwrap_or_default();
+        if weight == 0 {
+            return Err(ContractError::Unauthorized {});
+        }
+        let key_review = (key_proposal_id.as_slice(), key_address);
+        if REVIEWS.has(deps.storage, key_review) {
+            return Err(ContractError::AlreadyVoted {});
+        }
+        REVIEWS.save(deps.storage, key_review, &approved)?;
+
+        if approved {
+            proposal.yes_votes += Uint128::from(weight);
+        } else {
+            proposal.no_votes += Uint128::from(weight);
+        }
+        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("review")
+                    .add_attribute("reviewer", info.sender)
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("approved", approved.to_string())
+                    .add_attribute("weight", weight.to_string())
+                    .add_attribute("yes_votes", proposal.yes_votes)
+                    .add_attribute("no_votes", proposal.no_votes),
+            )
+            .add_attribute("action", "review")
+            .add_attribute("weight", weight.to_string()))
+    }
+
+    pub fn end_vote(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        // parameter changes close on their own schedule, neither held open nor
+        // cut short by the owner
+        let is_change = PARAMETER_CHANGES.has(deps.storage, key_proposal_id);
+        if info.sender != state.owner && !is_change {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut messages = vec![];
+        let mut event = Event::new("end_vote").add_attribute("proposal_id", proposal_id);
+        match PROPOSALS.may_load(deps.storage, key_proposal_id)? {
+            Some(mut proposal) => {
+                if is_change && env.block.time < proposal.vote_ends.unwrap_or_default() {
+                    return Err(ContractError::VotingPeriodNotOver {});
+                }
+                event = event.add_attribute("from", status_attr(&proposal.status));
+                match (&state.ballot_mode, &proposal.status) {
+                    // close the commit phase, the tally happens after the reveal period
+                    (BallotMode::CommitReveal { reveal_period, .. }, ProposalStatus::InProgress) => {
+                        proposal.status = ProposalStatus::Revealing;
+                        proposal.reveal_ends = Some(env.block.time.plus_seconds(*reveal_period));
+                        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+                        return Ok(Response::new()
+                            .add_event(event.add_attribute("to", status_attr(&proposal.status)))
+                            .add_attribute("action", "end_vote")
+                            .add_attribute("status", "revealing"));
+                    }
+                    (_, ProposalStatus::Revealing) => {
+                        if env.block.time < proposal.reveal_ends.unwrap_or_default() {
+                            return Err(ContractError::RevealPeriodNotOver {});
+                        }
+                        messages = settle_unrevealed(deps.storage, &state, proposal_id)?;
+                    }
+                    (_, ProposalStatus::InProgress) => {}
+                    _ => return Err(ContractError::ProposalNotInProgress {}),
+                }
+
+                if proposal.yes_votes > proposal.no_votes {
+                    proposal.status = ProposalStatus::Reviewed;
+                    proposal.review_height = env.block.height;
+                    if let Some(group) = &state.reviewer_group {
+                        let total: TotalWeightResponse = deps
+                            .querier
+                            .query_wasm_smart(group, &Cw4QueryMsg::TotalWeight {})?;
+                        proposal.review_weight = total.weight;
+                    }
+                    proposal.review_ends =
+                        Some(env.block.time.plus_seconds(PARAMETER_REVIEW_PERIOD));
                 } else {
                     proposal.status = ProposalStatus::Rejected;
                 }
+                event = event
+                    .add_attribute("to", status_attr(&proposal.status))
+                    .add_attribute("yes_votes", proposal.yes_votes)
+                    .add_attribute("no_votes", proposal.no_votes);
                 proposal.yes_votes = Uint128::from(0u128);
                 proposal.no_votes = Uint128::from(0u128);
                 PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
@@ -345,605 +1422,5315 @@ pub mod execute {
             None => return Err(ContractError::ProposalNotFound {}),
         };
 
-        Ok(Response::new().add_attribute("action", "end_review"))
+        Ok(Response::new()
+            .add_event(event)
+            .add_messages(messages)
+            .add_attribute("action", "end_vote"))
     }
 
-    pub fn funding_proposal(
+    // refunds or forfeits the deposits of ballots that were never revealed
+    fn settle_unrevealed(
+        storage: &mut dyn Storage,
+        state: &State,
+        proposal_id: Uint128,
+    ) -> StdResult<Vec<BankMsg>> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let unrevealed = COMMITMENTS
+            .prefix(key_proposal_id.as_slice())
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let refund = !matches!(
+            state.ballot_mode,
+            BallotMode::CommitReveal {
+                unrevealed: UnrevealedPolicy::Forfeit,
+                ..
+            }
+        );
+        let mut messages = vec![];
+        let mut forfeited = 0u128;
+        for (voter, commitment) in unrevealed {
+            COMMITMENTS.remove(storage, (key_proposal_id.as_slice(), voter.as_slice()));
+            if refund {
+                messages.push(BankMsg::Send {
+                    to_address: String::from_utf8(voter)?,
+                    amount: vec![coin(commitment.deposit.u128(), &state.denom)],
+                });
+            } else {
+                forfeited += commitment.deposit.u128();
+            }
+        }
+        if forfeited > 0 {
+            messages.push(BankMsg::Send {
+                to_address: state.owner.to_string(),
+                amount: vec![coin(forfeited, &state.denom)],
+            });
+        }
+        Ok(messages)
+    }
+
+    pub fn update_config(
         deps: DepsMut,
+        env: Env,
         info: MessageInfo,
-        proposal_id: Uint128,
+        ballot_mode: Option<BallotMode>,
+        voter_eligibility: Option<VoterEligibility>,
+        voting_mode: Option<VotingMode>,
+        ranking: Option<Ranking>,
     ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
 
-        Ok(Response::new().add_attribute("action", "funding_proposal"))
+        if !is_governor(&state, &env, &info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if let Some(ballot_mode) = ballot_mode {
+            state.ballot_mode = ballot_mode;
+        }
+        if let Some(voter_eligibility) = voter_eligibility {
+            if let VoterEligibility::Registry { contract } = &voter_eligibility {
+                deps.api.addr_validate(contract.as_str())?;
+            }
+            state.voter_eligibility = voter_eligibility;
+        }
+        if let Some(voting_mode) = voting_mode {
+            match &voting_mode {
+                VotingMode::Snapshot { contract } => {
+                    deps.api.addr_validate(contract.as_str())?;
+                }
+                VotingMode::Conviction { decay, threshold }
+                    if *decay >= Decimal::one() || threshold.is_zero() =>
+                {
+                    return Err(ContractError::InvalidConviction {});
+                }
+                _ => {}
+            }
+            state.voting_mode = voting_mode;
+        }
+        if let Some(ranking) = ranking {
+            state.ranking = Some(ranking);
+        }
+        // revealed votes are always priced quadratically
+        if state.ballot_mode != BallotMode::Open && state.voting_mode != VotingMode::Quadratic {
+            return Err(ContractError::UnsupportedVotingMode {});
+        }
+        STATE.save(deps.storage, &state)?;
 
+        Ok(Response::new()
+            .add_event(Event::new("update_config").add_attribute("sender", info.sender))
+            .add_attribute("action", "update_config"))
     }
 
-}
+    pub fn update_allowlist(
+        deps: DepsMut,
+        info: MessageInfo,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_binary(&STATE.load(deps.storage)?),
-        QueryMsg::TokenStake { address } => {
-            query::token_balance(deps, deps.api.addr_validate(address.as_str())?)
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
         }
-        QueryMsg::GetProposal { proposal_id } => {
-            to_binary(&query::get_proposal(deps, proposal_id)?)
+        for voter in add {
+            let voter = deps.api.addr_validate(&voter)?;
+            ALLOWLIST.save(deps.storage, voter.as_str().as_bytes(), &true)?;
+        }
+        for voter in remove {
+            let voter = deps.api.addr_validate(&voter)?;
+            ALLOWLIST.remove(deps.storage, voter.as_str().as_bytes());
         }
+
+        Ok(Response::new()
+            .add_event(Event::new("update_allowlist").add_attribute("sender", info.sender))
+            .add_attribute("action", "update_allowlist"))
     }
-}
 
-pub mod query {
-    use super::*;
+    pub fn set_trust_weights(
+        deps: DepsMut,
+        info: MessageInfo,
+        weights: Vec<(String, Decimal)>,
+        default: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
 
-    pub fn get_proposal(deps: Deps, proposal_id: Uint128) -> StdResult<GetProposalResponse> {
-        let key = &proposal_id.to_be_bytes();
-        let proposal = PROPOSALS.load(deps.storage, key)?;
-        Ok(GetProposalResponse {
-            status: proposal.status,
-            yes_votes: proposal.yes_votes,
-            no_votes: proposal.no_votes,
-        })
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        for (voter, weight) in weights {
+            let voter = deps.api.addr_validate(&voter)?;
+            if weight > Decimal::one() {
+                return Err(ContractError::InvalidTrustWeight {});
+            }
+            TRUST_WEIGHTS.save(deps.storage, voter.as_str().as_bytes(), &weight)?;
+        }
+        if let Some(default) = default {
+            if default > Decimal::one() {
+                return Err(ContractError::InvalidTrustWeight {});
+            }
+            state.default_trust_weight = default;
+            STATE.save(deps.storage, &state)?;
+        }
+
+        Ok(Response::new()
+            .add_event(Event::new("set_trust_weights").add_attribute("sender", info.sender))
+            .add_attribute("action", "set_trust_weights"))
     }
 
-    pub fn token_balance(deps: Deps, address: Addr) -> StdResult<Binary> {
-        let token_manager = BALANCES
-            .may_load(deps.storage, address.as_str().as_bytes())?
-            .unwrap_or_default();
+    pub fn commit_vote(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+        commitment: Binary,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let key_address = info.sender.as_str().as_bytes();
+        let state = STATE.load(deps.storage)?;
 
-        let resp = TokenStakeResponse {
-            token_balance: token_manager.token_balance,
+        if state.ballot_mode == BallotMode::Open {
+            return Err(ContractError::CommitRevealDisabled {});
+        }
+        if !is_eligible(deps.as_ref(), &state, &info.sender)? {
+            return Err(ContractError::VoterNotEligible {});
+        }
+        let proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if proposal.status != ProposalStatus::InProgress {
+            return Err(ContractError::ProposalNotInProgress {});
+        }
+        let key_commitment = (key_proposal_id.as_slice(), key_address);
+        if COMMITMENTS.has(deps.storage, key_commitment) {
+            return Err(ContractError::AlreadyCommitted {});
+        }
+        let deposit = extract_budget_coin(&info.funds, &state.denom)?;
+
+        COMMITMENTS.save(
+            deps.storage,
+            key_commitment,
+            &Commitment {
+                commitment,
+                deposit: deposit.amount,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("commit_vote")
+                    .add_attribute("voter", info.sender)
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("deposit", deposit.amount),
+            )
+            .add_attribute("action", "commit_vote")
+            .add_attribute("proposal_id", proposal_id))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn reveal_vote(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        proposal_id: Uint128,
+        votes: u64,
+        yes: bool,
+        salt: String,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let key_address = info.sender.as_str().as_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let mut proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if proposal.status != ProposalStatus::Revealing {
+            return Err(ContractError::ProposalNotRevealing {});
+        }
+        if env.block.time >= proposal.reveal_ends.unwrap_or_default() {
+            return Err(ContractError::RevealPeriodOver {});
+        }
+        let key_commitment = (key_proposal_id.as_slice(), key_address);
+        let commitment = COMMITMENTS
+            .may_load(deps.storage, key_commitment)?
+            .ok_or(ContractError::CommitmentNotFound {})?;
+
+        let preimage = format!("{}:{}:{}:{}:{}", info.sender, proposal_id, votes, yes, salt);
+        if Sha256::digest(preimage.as_bytes())[..] != commitment.commitment[..] {
+            return Err(ContractError::CommitmentMismatch {});
+        }
+
+        let mut voter = VOTERS
+            .may_load(deps.storage, key_address)?
+            .unwrap_or(Voter {
+                vote_count: vec![],
+                against: vec![],
+            });
+        if !yes {
+            count_against(&mut voter, proposal_id, votes);
+        }
+        let previous = match voter.vote_count.iter().position(|&x| x.0 == proposal_id) {
+            Some(index) => {
+                let previous = voter.vote_count[index].1;
+                let total = previous
+                    .checked_add(votes)
+                    .ok_or(ContractError::OverflowError {})?;
+                voter.vote_count[index] = (proposal_id, total);
+                previous
+            }
+            None => {
+                voter.vote_count.push((proposal_id, votes));
+                0
+            }
+        };
+        let cost = quadratic_cost(&state, previous, votes)?;
+        if cost > commitment.deposit.u128() {
+            return Err(ContractError::InsufficientFundsSent {});
+        }
+
+        if yes {
+            proposal.yes_votes += Uint128::from(votes);
+        } else {
+            proposal.no_votes += Uint128::from(votes);
+        }
+        // the fee is taken from the revealed cost like from an open vote
+        let fee = collect_fee(deps.storage, &state, Uint128::from(cost))?;
+        let stake = Uint128::from(cost) - fee;
+        let mut balance_member = BALANCES
+            .may_load(deps.storage, key_address)?
+            .unwrap_or_default();
+        balance_member.token_balance += stake;
+        lock_tokens(&mut balance_member, proposal_id, stake);
+        if !yes {
+            lock_against(&mut balance_member, proposal_id, stake);
+        }
+        if !proposal.voters.contains(&info.sender) {
+            proposal.voters.push(info.sender.clone());
+        }
+
+        COMMITMENTS.remove(deps.storage, key_commitment);
+        VOTERS.save(deps.storage, key_address, &voter)?;
+        BALANCES.save(deps.storage, key_address, &balance_member)?;
+        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+
+        let mut res = Response::new().add_event(
+            Event::new("reveal_vote")
+                .add_attribute("voter", info.sender.clone())
+                .add_attribute("proposal_id", proposal_id)
+                .add_attribute("yes_vote", yes.to_string())
+                .add_attribute("votes", votes.to_string())
+                .add_attribute("amount", cost.to_string())
+                .add_attribute("fee", fee)
+                .add_attribute("yes_votes", proposal.yes_votes)
+                .add_attribute("no_votes", proposal.no_votes),
+        );
+        let refund = commitment.deposit.u128() - cost;
+        if refund > 0 {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(refund, &state.denom)],
+            });
+        }
+        Ok(res
+            .add_attribute("action", "reveal_vote")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("votes", votes.to_string()))
+    }
+
+    // price of `votes` more votes after `previous` ones, the k-th vote costs k^2
+    // units, so it is the difference of the sums of squares up to both counts
+    fn quadratic_cost(state: &State, previous: u64, votes: u64) -> Result<u128, ContractError> {
+        let total = previous
+            .checked_add(votes)
+            .ok_or(ContractError::OverflowError {})?;
+        // n(n + 1)(2n + 1) / 6
+        let sum_of_squares = |n: u64| {
+            let n = u128::from(n);
+            n.checked_mul(n + 1)?
+                .checked_mul(2 * n + 1)
+                .map(|sum| sum / 6)
+        };
+        sum_of_squares(total)
+            .zip(sum_of_squares(previous))
+            .and_then(|(total, previous)| (total - previous).checked_mul(state.unit_price.u128()))
+            .ok_or(ContractError::OverflowError {})
+    }
+
+    pub fn end_review(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let change = PARAMETER_CHANGES.may_load(deps.storage, key_proposal_id)?;
+        if info.sender != state.owner && change.is_none() {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut messages = vec![];
+        let mut event = Event::new("end_review").add_attribute("proposal_id", proposal_id);
+        match PROPOSALS.may_load(deps.storage, key_proposal_id)? {
+            Some(mut proposal) => {
+                if proposal.status != ProposalStatus::Reviewed {
+                    return Err(ContractError::ProposalNotInReview {});
+                }
+                if change.is_some() && env.block.time < proposal.review_ends.unwrap_or_default() {
+                    return Err(ContractError::ReviewPeriodNotOver {});
+                }
+
+                if proposal.yes_votes > proposal.no_votes {
+                    proposal.status = ProposalStatus::Passed;
+                    if let Some(change) = change {
+                        messages = parameter_change_msgs(&env, change)?;
+                    }
+                } else {
+                    proposal.status = ProposalStatus::Rejected;
+                }
+                event = event
+                    .add_attribute("from", status_attr(&ProposalStatus::Reviewed))
+                    .add_attribute("to", status_attr(&proposal.status))
+                    .add_attribute("yes_votes", proposal.yes_votes)
+                    .add_attribute("no_votes", proposal.no_votes)
+                    .add_attribute("messages", messages.len().to_string());
+                proposal.yes_votes = Uint128::from(0u128);
+                proposal.no_votes = Uint128::from(0u128);
+                PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+            }
+            None => return Err(ContractError::ProposalNotFound {}),
+        };
+
+        Ok(Response::new()
+            .add_event(event)
+            .add_messages(messages)
+            .add_attribute("action", "end_review"))
+    }
+
+    // config updates go through the contract's own UpdateConfig, which accepts
+    // the contract as sender
+    pub fn parameter_change_msgs(env: &Env, change: ParameterChange) -> StdResult<Vec<CosmosMsg>> {
+        match change {
+            ParameterChange::UpdateConfig {
+                ballot_mode,
+                voter_eligibility,
+                voting_mode,
+                ranking,
+            } => Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_json_binary(&ExecuteMsg::UpdateConfig {
+                    ballot_mode,
+                    voter_eligibility,
+                    voting_mode,
+                    ranking,
+                })?,
+                funds: vec![],
+            })]),
+            ParameterChange::UpdateReviewers { add, remove } => {
+                Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    msg: to_json_binary(&ExecuteMsg::UpdateReviewers { add, remove })?,
+                    funds: vec![],
+                })])
+            }
+            ParameterChange::UpdateMilestoneThreshold { threshold } => {
+                Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    msg: to_json_binary(&ExecuteMsg::UpdateMilestoneThreshold { threshold })?,
+                    funds: vec![],
+                })])
+            }
+            ParameterChange::UpdateUnitPrice { unit_price } => {
+                Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    msg: to_json_binary(&ExecuteMsg::UpdateUnitPrice { unit_price })?,
+                    funds: vec![],
+                })])
+            }
+            ParameterChange::Execute { msgs } => Ok(msgs),
+        }
+    }
+
+    pub fn propose_parameter_change(
+        deps: DepsMut,
+        env: Env,
+        proposal_id: Uint128,
+        change: ParameterChange,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+
+        if PROPOSALS.has(deps.storage, key_proposal_id) {
+            return Err(ContractError::ProposalAlreadyExists {});
+        }
+        // checked up front so the change cannot fail once it passed
+        match &change {
+            ParameterChange::UpdateReviewers { add, remove } => {
+                for reviewer in add.iter().chain(remove) {
+                    deps.api.addr_validate(reviewer)?;
+                }
+            }
+            ParameterChange::UpdateMilestoneThreshold { threshold }
+                if *threshold >= Decimal::one() =>
+            {
+                return Err(ContractError::InvalidThreshold {});
+            }
+            ParameterChange::UpdateUnitPrice { unit_price } if unit_price.is_zero() => {
+                return Err(ContractError::InvalidUnitPrice {});
+            }
+            _ => {}
+        }
+        PROPOSALS.save(
+            deps.storage,
+            key_proposal_id,
+            &Proposal {
+                fund_address: env.contract.address.into_string(),
+                status: ProposalStatus::InProgress,
+                start_height: env.block.height,
+                vote_ends: Some(env.block.time.plus_seconds(PARAMETER_VOTING_PERIOD)),
+                ..Default::default()
+            },
+        )?;
+        PARAMETER_CHANGES.save(deps.storage, key_proposal_id, &change)?;
+
+        Ok(Response::new()
+            .add_event(Event::new("propose_parameter_change").add_attribute("proposal_id", proposal_id))
+            .add_attribute("action", "propose_parameter_change")
+            .add_attribute("proposal_id", proposal_id))
+    }
+
+    pub fn propose(
+        deps: DepsMut,
+        env: Env,
+        proposal_id: Uint128,
+        fund_address: String,
+        msgs: Vec<CosmosMsg>,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+
+        if PROPOSALS.has(deps.storage, key_proposal_id) {
+            return Err(ContractError::ProposalAlreadyExists {});
+        }
+        if msgs.is_empty() {
+            return Err(ContractError::NothingToExecute {});
+        }
+        let fund_address = deps.api.addr_validate(&fund_address)?;
+        let event = Event::new("propose")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("fund_address", fund_address.clone())
+            .add_attribute("messages", msgs.len().to_string());
+        PROPOSALS.save(
+            deps.storage,
+            key_proposal_id,
+            &Proposal {
+                fund_address: fund_address.into_string(),
+                status: ProposalStatus::InProgress,
+                start_height: env.block.height,
+                ..Default::default()
+            },
+        )?;
+        PROPOSAL_MSGS.save(deps.storage, key_proposal_id, &msgs)?;
+
+        Ok(Response::new()
+            .add_event(event)
+            .add_attribute("action", "propose")
+            .add_attribute("proposal_id", proposal_id))
+    }
+
+    pub fn execute_proposal(
+        deps: DepsMut,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+
+        let mut proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        match proposal.status {
+            ProposalStatus::Passed => {}
+            ProposalStatus::Executed => return Err(ContractError::AlreadyExecuted {}),
+            _ => return Err(ContractError::ProposalNotPassed {}),
+        }
+        let msgs = PROPOSAL_MSGS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::NothingToExecute {})?;
+        proposal.status = ProposalStatus::Executed;
+        PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("execute_proposal")
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("messages", msgs.len().to_string())
+                    .add_attribute("to", status_attr(&proposal.status)),
+            )
+            .add_messages(msgs)
+            .add_attribute("action", "execute_proposal")
+            .add_attribute("proposal_id", proposal_id))
+    }
+
+    pub fn funding_proposal(
+        deps: DepsMut,
+        info: MessageInfo,
+        round_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_round_id = &round_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let round = ROUNDS
+            .may_load(deps.storage, key_round_id)?
+            .ok_or(ContractError::RoundNotFound {})?;
+        if DISTRIBUTIONS.has(deps.storage, key_round_id) {
+            return Err(ContractError::RoundAlreadyDistributed {});
+        }
+        // every proposal must be passed or rejected, so only passed ones get matched
+        for proposal_id in &round.proposals {
+            let proposal = PROPOSALS.load(deps.storage, &proposal_id.to_be_bytes())?;
+            if !matches!(
+                proposal.status,
+                ProposalStatus::Passed | ProposalStatus::Rejected | ProposalStatus::Executed
+            ) {
+                return Err(ContractError::RoundNotFinalized {
+                    proposal_id: proposal_id.to_string(),
+                });
+            }
+        }
+
+        let (mut grants, leftover) = estimate_round(deps.as_ref(), round_id, &round)?;
+        let fees = deduct_fees(&state, &mut grants);
+        let distribution = Distribution {
+            grants: grants
+                .into_iter()
+                .zip(fees)
+                .map(|((proposal_id, grant), fee)| GrantPayout {
+                    proposal_id,
+                    grant,
+                    fee,
+                    claimed: false,
+                })
+                .collect(),
+            leftover,
+        };
+        DISTRIBUTIONS.save(deps.storage, key_round_id, &distribution)?;
+
+        // one event per grant, the totals go on the round event
+        let grant_events = distribution.grants.iter().map(|payout| {
+            Event::new("grant")
+                .add_attribute("round_id", round_id)
+                .add_attribute("proposal_id", payout.proposal_id)
+                .add_attribute("recipient", payout.grant.addr.clone())
+                .add_attribute("grant", payout.grant.grant.to_string())
+                .add_attribute("fee", payout.fee)
+                .add_attribute(
+                    "collected_vote_funds",
+                    payout.grant.collected_vote_funds.to_string(),
+                )
+                .add_attribute(
+                    "eligible",
+                    payout.grant.ineligible_reasons.is_empty().to_string(),
+                )
+        });
+        Ok(Response::new()
+            .add_event(
+                Event::new("funding_proposal")
+                    .add_attribute("round_id", round_id)
+                    .add_attribute("grants", distribution.grants.len().to_string())
+                    .add_attribute("leftover", leftover),
+            )
+            .add_events(grant_events)
+            .add_attribute("action", "funding_proposal")
+            .add_attribute("round_id", round_id)
+            .add_attribute("leftover", leftover))
+    }
+
+    // pays every unclaimed grant of the round that belongs to the sender
+    pub fn claim_grant(
+        deps: DepsMut,
+        info: MessageInfo,
+        round_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_round_id = &round_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let mut distribution = DISTRIBUTIONS
+            .may_load(deps.storage, key_round_id)?
+            .ok_or(ContractError::RoundNotDistributed {})?;
+
+        let round = ROUNDS.load(deps.storage, key_round_id)?;
+
+        let mut payout = 0u128;
+        let mut escrowed = 0u128;
+        let mut streamed = 0u128;
+        let mut fees = Uint128::zero();
+        for payout_grant in distribution.grants.iter_mut() {
+            if payout_grant.grant.addr != info.sender.as_str() || payout_grant.claimed {
+                continue;
+            }
+            // vote funds were charged when paid, the fee on the match was fixed
+            // by the distribution and is booked now
+            book_fee(deps.storage, payout_grant.fee)?;
+            fees += payout_grant.fee;
+            let amount = payout_grant.grant.grant + payout_grant.grant.collected_vote_funds;
+            let key_proposal_id = &payout_grant.proposal_id.to_be_bytes();
+            // grants with milestones are released tranche by tranche
+            match ESCROWS.may_load(deps.storage, key_proposal_id)? {
+                Some(mut escrow) => {
+                    escrow.amount = Uint128::from(amount);
+                    ESCROWS.save(deps.storage, key_proposal_id, &escrow)?;
+                    escrowed += amount;
+                }
+                None if round.milestones_required => {
+                    return Err(ContractError::MilestonesRequired {
+                        proposal_id: payout_grant.proposal_id.to_string(),
+                    })
+                }
+                None => match &round.vesting {
+                    Some(vesting) => {
+                        let stream = Stream {
+                            round_id,
+                            amount: Uint128::from(amount),
+                            claimed: Uint128::zero(),
+                            vesting: vesting.clone(),
+                        };
+                        STREAMS.save(deps.storage, key_proposal_id, &stream)?;
+                        streamed += amount;
+                    }
+                    None => payout += amount,
+                },
+            }
+            payout_grant.claimed = true;
+        }
+        if payout == 0 && escrowed == 0 && streamed == 0 {
+            return Err(ContractError::NothingToClaim {});
+        }
+        DISTRIBUTIONS.save(deps.storage, key_round_id, &distribution)?;
+
+        let mut res = Response::new();
+        if payout > 0 {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(payout, &state.denom)],
+            });
+        }
+        Ok(res
+            .add_event(
+                Event::new("claim_grant")
+                    .add_attribute("recipient", info.sender)
+                    .add_attribute("round_id", round_id)
+                    .add_attribute("amount", payout.to_string())
+                    .add_attribute("escrowed", escrowed.to_string())
+                    .add_attribute("streamed", streamed.to_string())
+                    .add_attribute("fee", fees),
+            )
+            .add_attribute("action", "claim_grant")
+            .add_attribute("round_id", round_id)
+            .add_attribute("amount", payout.to_string())
+            .add_attribute("escrowed", escrowed.to_string())
+            .add_attribute("streamed", streamed.to_string()))
+    }
+
+    pub fn update_reviewers(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        if !is_governor(&state, &env, &info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        for reviewer in add {
+            let reviewer = deps.api.addr_validate(&reviewer)?;
+            if !state.reviewers.contains(&reviewer) {
+                state.reviewers.push(reviewer);
+            }
+        }
+        for reviewer in remove {
+            let reviewer = deps.api.addr_validate(&reviewer)?;
+            state.reviewers.retain(|r| *r != reviewer);
+        }
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_event(Event::new("update_reviewers").add_attribute("sender", info.sender))
+            .add_attribute("action", "update_reviewers"))
+    }
+
+    pub fn update_reviewer_group(
+        deps: DepsMut,
+        info: MessageInfo,
+        group: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.reviewer_group = group
+            .map(|group| deps.api.addr_validate(&group))
+            .transpose()?;
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_event(Event::new("update_reviewer_group").add_attribute("sender", info.sender))
+            .add_attribute("action", "update_reviewer_group"))
+    }
+
+    pub fn set_paused(
+        deps: DepsMut,
+        info: MessageInfo,
+        paused: bool,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        if info.sender != state.owner && Some(&info.sender) != state.guardian.as_ref() {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.paused = paused;
+        STATE.save(deps.storage, &state)?;
+
+        let action = if paused { "pause" } else { "unpause" };
+        Ok(Response::new()
+            .add_event(Event::new(action).add_attribute("sender", info.sender))
+            .add_attribute("action", action))
+    }
+
+    pub fn update_fee(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        fee: Option<PlatformFee>,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        if !is_governor(&state, &env, &info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if let Some(fee) = &fee {
+            if fee.bps > MAX_FEE_BPS {
+                return Err(ContractError::FeeTooHigh {});
+            }
+            deps.api.addr_validate(fee.treasury.as_str())?;
+        }
+        let bps = fee.as_ref().map(|fee| fee.bps).unwrap_or_default();
+        // fees collected so far go to the treasury they were collected for
+        let mut messages = vec![];
+        if let Some(old) = &state.fee {
+            let treasury = TREASURY.may_load(deps.storage)?.unwrap_or_default();
+            let moved = fee.as_ref().map(|fee| &fee.treasury) != Some(&old.treasury);
+            if moved && !treasury.is_zero() {
+                TREASURY.save(deps.storage, &Uint128::zero())?;
+                messages.push(BankMsg::Send {
+                    to_address: old.treasury.to_string(),
+                    amount: vec![coin(treasury.u128(), &state.denom)],
+                });
+            }
+        }
+        state.fee = fee;
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_event(
+                Event::new("update_fee")
+                    .add_attribute("sender", info.sender)
+                    .add_attribute("bps", bps.to_string()),
+            )
+            .add_attribute("action", "update_fee"))
+    }
+
+    pub fn withdraw_fees(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let treasury = match &state.fee {
+            Some(fee) => &fee.treasury,
+            None => return Err(ContractError::Unauthorized {}),
+        };
+        if info.sender != *treasury {
+            return Err(ContractError::Unauthorized {});
+        }
+        let amount = TREASURY.may_load(deps.storage)?.unwrap_or_default();
+        if amount.is_zero() {
+            return Err(ContractError::NothingToClaim {});
+        }
+        TREASURY.save(deps.storage, &Uint128::zero())?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("withdraw_fees")
+                    .add_attribute("treasury", info.sender.clone())
+                    .add_attribute("amount", amount),
+            )
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(amount.u128(), &state.denom)],
+            })
+            .add_attribute("action", "withdraw_fees")
+            .add_attribute("amount", amount))
+    }
+
+    pub fn withdraw_leftover(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        round_id: Uint128,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        let key_round_id = &round_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        if !is_governor(&state, &env, &info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        let recipient = deps.api.addr_validate(&recipient)?;
+        let mut distribution = DISTRIBUTIONS
+            .may_load(deps.storage, key_round_id)?
+            .ok_or(ContractError::RoundNotDistributed {})?;
+        let amount = distribution.leftover;
+        if amount.is_zero() {
+            return Err(ContractError::NothingToClaim {});
+        }
+        distribution.leftover = Uint128::zero();
+        DISTRIBUTIONS.save(deps.storage, key_round_id, &distribution)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("withdraw_leftover")
+                    .add_attribute("round_id", round_id)
+                    .add_attribute("recipient", recipient.clone())
+                    .add_attribute("amount", amount),
+            )
+            .add_message(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![coin(amount.u128(), &state.denom)],
+            })
+            .add_attribute("action", "withdraw_leftover")
+            .add_attribute("round_id", round_id)
+            .add_attribute("amount", amount))
+    }
+
+    // rejected proposals are never matched nor paid, so their stake goes back
+    pub fn refund_contribution(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_sender = info.sender.as_str().as_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let proposal = PROPOSALS
+            .may_load(deps.storage, &proposal_id.to_be_bytes())?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if proposal.status != ProposalStatus::Rejected {
+            return Err(ContractError::ProposalNotRejected {});
+        }
+        let mut balance = BALANCES
+            .may_load(deps.storage, key_sender)?
+            .ok_or(ContractError::NothingToRefund {})?;
+        let index = balance
+            .locked_tokens
+            .iter()
+            .position(|(id, _)| *id == proposal_id)
+            .ok_or(ContractError::NothingToRefund {})?;
+        let (_, amount) = balance.locked_tokens.remove(index);
+        balance.locked_against.retain(|(id, _)| *id != proposal_id);
+        balance.token_balance = balance
+            .token_balance
+            .checked_sub(amount)
+            .map_err(|_| ContractError::OverflowError {})?;
+        BALANCES.save(deps.storage, key_sender, &balance)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("refund_contribution")
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("voter", info.sender.clone())
+                    .add_attribute("amount", amount),
+            )
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(amount.u128(), &state.denom)],
+            })
+            .add_attribute("action", "refund_contribution")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("amount", amount))
+    }
+
+    pub fn update_guardian(
+        deps: DepsMut,
+        info: MessageInfo,
+        guardian: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.guardian = guardian
+            .map(|guardian| deps.api.addr_validate(&guardian))
+            .transpose()?;
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_event(Event::new("update_guardian").add_attribute("sender", info.sender))
+            .add_attribute("action", "update_guardian"))
+    }
+
+    pub fn set_milestones(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+        milestones: Vec<Decimal>,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+
+        let proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if info.sender.as_str() != proposal.fund_address {
+            return Err(ContractError::Unauthorized {});
+        }
+        if !matches!(
+            proposal.status,
+            ProposalStatus::Passed | ProposalStatus::Executed
+        ) {
+            return Err(ContractError::ProposalNotPassed {});
+        }
+        let round_id = proposal.round_id.ok_or(ContractError::RoundNotFound {})?;
+        if let Some(escrow) = ESCROWS.may_load(deps.storage, key_proposal_id)? {
+            if !escrow.amount.is_zero() {
+                return Err(ContractError::EscrowAlreadyFunded {});
+            }
+        }
+        let total = milestones
+            .iter()
+            .fold(Decimal::zero(), |total, percentage| total + *percentage);
+        if milestones.is_empty()
+            || milestones.iter().any(|m| m.is_zero())
+            || total != Decimal::one()
+        {
+            return Err(ContractError::InvalidMilestones {});
+        }
+
+        let escrow = Escrow {
+            round_id,
+            amount: Uint128::zero(),
+            milestones: milestones
+                .into_iter()
+                .map(|percentage| Milestone {
+                    percentage,
+                    status: MilestoneStatus::Pending,
+                    approvals: vec![],
+                    rejections: vec![],
+                    approved_weight: 0,
+                    rejected_weight: 0,
+                })
+                .collect(),
+        };
+        ESCROWS.save(deps.storage, key_proposal_id, &escrow)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("set_milestones")
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("milestones", escrow.milestones.len().to_string()),
+            )
+            .add_attribute("action", "set_milestones")
+            .add_attribute("proposal_id", proposal_id))
+    }
+
+    pub fn approve_milestone(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+        milestone: u32,
+        approved: bool,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        // the committee that reviewed the proposal also releases its milestones
+        let (weight, total) = match &state.reviewer_group {
+            Some(group) => {
+                let member: MemberResponse = deps.querier.query_wasm_smart(
+                    group,
+                    &Cw4QueryMsg::Member {
+                        addr: info.sender.to_string(),
+                        at_height: Some(proposal.review_height),
+                    },
+                )?;
+                (member.weight.unwrap_or_default(), proposal.review_weight)
+            }
+            None => (
+                u64::from(state.reviewers.contains(&info.sender)),
+                state.reviewers.len() as u64,
+            ),
+        };
+        if weight == 0 {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut escrow = ESCROWS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::EscrowNotFound {})?;
+        if escrow.amount.is_zero() {
+            return Err(ContractError::EscrowNotFunded {});
+        }
+        let index = milestone as usize;
+        let tranche = match escrow.milestones.get(index) {
+            Some(m) if m.status == MilestoneStatus::Pending => escrow.tranche(index),
+            Some(_) => return Err(ContractError::MilestoneNotPending {}),
+            None => return Err(ContractError::MilestoneNotFound {}),
+        };
+        let entry = &mut escrow.milestones[index];
+        if entry.approvals.contains(&info.sender) || entry.rejections.contains(&info.sender) {
+            return Err(ContractError::MilestoneAlreadyReviewed {});
+        }
+        if approved {
+            entry.approvals.push(info.sender.clone());
+            entry.approved_weight += weight;
+        } else {
+            entry.rejections.push(info.sender.clone());
+            entry.rejected_weight += weight;
+        }
+        let total = Uint128::from(total);
+        let needed = total * state.milestone_threshold;
+        if Uint128::from(entry.approved_weight) > needed {
+            entry.status = MilestoneStatus::Approved;
+        } else if total.saturating_sub(Uint128::from(entry.rejected_weight)) <= needed {
+            entry.status = MilestoneStatus::Rejected;
+            return_to_pool(deps.storage, escrow.round_id, tranche)?;
+        }
+        let status = escrow.milestones[index].status.clone();
+        ESCROWS.save(deps.storage, key_proposal_id, &escrow)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("approve_milestone")
+                    .add_attribute("reviewer", info.sender)
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("milestone", milestone.to_string())
+                    .add_attribute("approved", approved.to_string())
+                    .add_attribute("tranche", tranche)
+                    .add_attribute("status", milestone_status_attr(&status)),
+            )
+            .add_attribute("action", "approve_milestone")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("milestone", milestone.to_string())
+            .add_attribute("approved", approved.to_string()))
+    }
+
+    pub fn update_milestone_threshold(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        threshold: Decimal,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        if !is_governor(&state, &env, &info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if threshold >= Decimal::one() {
+            return Err(ContractError::InvalidThreshold {});
+        }
+        state.milestone_threshold = threshold;
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("update_milestone_threshold")
+                    .add_attribute("threshold", threshold.to_string()),
+            )
+            .add_attribute("action", "update_milestone_threshold"))
+    }
+
+    pub fn update_unit_price(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        unit_price: Uint128,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        if !is_governor(&state, &env, &info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if unit_price.is_zero() {
+            return Err(ContractError::InvalidUnitPrice {});
+        }
+        state.unit_price = unit_price;
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_event(Event::new("update_unit_price").add_attribute("unit_price", unit_price))
+            .add_attribute("action", "update_unit_price"))
+    }
+
+    pub fn claim_milestone(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if info.sender.as_str() != proposal.fund_address {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut escrow = ESCROWS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::EscrowNotFound {})?;
+
+        let mut payout = Uint128::zero();
+        for index in 0..escrow.milestones.len() {
+            if escrow.milestones[index].status == MilestoneStatus::Approved {
+                payout += escrow.tranche(index);
+                escrow.milestones[index].status = MilestoneStatus::Claimed;
+            }
+        }
+        if payout.is_zero() {
+            return Err(ContractError::NothingToClaim {});
+        }
+        ESCROWS.save(deps.storage, key_proposal_id, &escrow)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("claim_milestone")
+                    .add_attribute("recipient", info.sender.clone())
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("amount", payout),
+            )
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(payout.u128(), &state.denom)],
+            })
+            .add_attribute("action", "claim_milestone")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("amount", payout))
+    }
+
+    pub fn close_escrow(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut escrow = ESCROWS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::EscrowNotFound {})?;
+        if escrow.amount.is_zero() {
+            return Err(ContractError::EscrowNotFunded {});
+        }
+
+        let mut returned = Uint128::zero();
+        for index in 0..escrow.milestones.len() {
+            if matches!(
+                escrow.milestones[index].status,
+                MilestoneStatus::Pending | MilestoneStatus::Approved
+            ) {
+                returned += escrow.tranche(index);
+                escrow.milestones[index].status = MilestoneStatus::Rejected;
+            }
+        }
+        return_to_pool(deps.storage, escrow.round_id, returned)?;
+        ESCROWS.save(deps.storage, key_proposal_id, &escrow)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("close_escrow")
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("returned", returned),
+            )
+            .add_attribute("action", "close_escrow")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("returned", returned))
+    }
+
+    pub fn claim_vested(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        let proposal = PROPOSALS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if info.sender.as_str() != proposal.fund_address {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut stream = STREAMS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::StreamNotFound {})?;
+
+        let payout = stream.vested(env.block.time) - stream.claimed;
+        if payout.is_zero() {
+            return Err(ContractError::NothingToClaim {});
+        }
+        stream.claimed += payout;
+        STREAMS.save(deps.storage, key_proposal_id, &stream)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("claim_vested")
+                    .add_attribute("recipient", info.sender.clone())
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("amount", payout),
+            )
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(payout.u128(), &state.denom)],
+            })
+            .add_attribute("action", "claim_vested")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("amount", payout))
+    }
+
+    pub fn claw_back(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        proposal_id: Uint128,
+    ) -> Result<Response, ContractError> {
+        let key_proposal_id = &proposal_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut stream = STREAMS
+            .may_load(deps.storage, key_proposal_id)?
+            .ok_or(ContractError::StreamNotFound {})?;
+
+        // freeze the stream at what has vested so far
+        let now = env.block.time;
+        let vested = stream.vested(now);
+        let unvested = stream.amount - vested;
+        stream.amount = vested;
+        if now < stream.vesting.end {
+            stream.vesting.end = now.max(stream.vesting.start);
+        }
+        return_to_pool(deps.storage, stream.round_id, unvested)?;
+        STREAMS.save(deps.storage, key_proposal_id, &stream)?;
+
+        Ok(Response::new()
+            .add_event(
+                Event::new("claw_back")
+                    .add_attribute("proposal_id", proposal_id)
+                    .add_attribute("returned", unvested),
+            )
+            .add_attribute("action", "claw_back")
+            .add_attribute("proposal_id", proposal_id)
+            .add_attribute("returned", unvested))
+    }
+
+    fn return_to_pool(
+        storage: &mut dyn Storage,
+        round_id: Uint128,
+        amount: Uint128,
+    ) -> StdResult<()> {
+        DISTRIBUTIONS.update(storage, &round_id.to_be_bytes(), |distribution| {
+            let mut distribution =
+                distribution.ok_or_else(|| StdError::not_found("Distribution"))?;
+            distribution.leftover += amount;
+            Ok::<_, StdError>(distribution)
+        })?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_round(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        round_id: Uint128,
+        proposals: Vec<(Uint128, String)>,
+        algorithm: QuadraticFundingAlgorithm,
+        milestones_required: bool,
+        vesting: Option<Vesting>,
+        retro: Option<RetroFunding>,
+    ) -> Result<Response, ContractError> {
+        let key_round_id = &round_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        if ROUNDS.has(deps.storage, key_round_id) {
+            return Err(ContractError::RoundAlreadyExists {});
+        }
+        let budget = extract_budget_coin(&info.funds, &state.denom)?;
+        if let Some(vesting) = &vesting {
+            if vesting.end <= vesting.start {
+                return Err(ContractError::InvalidVesting {});
+            }
+        }
+        if let Some(retro) = &retro {
+            for badgeholder in &retro.badgeholders {
+                deps.api.addr_validate(badgeholder.as_str())?;
+            }
+        }
+
+        let mut proposal_ids = vec![];
+        for (proposal_id, fund_address) in proposals {
+            let key_proposal_id = &proposal_id.to_be_bytes();
+            let fund_address = deps.api.addr_validate(&fund_address)?;
+
+            // proposals may already exist from earlier votes
+            let mut proposal = PROPOSALS
+                .may_load(deps.storage, key_proposal_id)?
+                .unwrap_or(Proposal {
+                    // retro rounds fund projects that are already completed
+                    status: if retro.is_some() {
+                        ProposalStatus::Passed
+                    } else {
+                        ProposalStatus::InProgress
+                    },
+                    start_height: env.block.height,
+                    ..Default::default()
+                });
+            if proposal.round_id.is_some() {
+                return Err(ContractError::ProposalAlreadyInRound {
+                    proposal_id: proposal_id.to_string(),
+                });
+            }
+            proposal.fund_address = fund_address.into_string();
+            proposal.round_id = Some(round_id);
+            PROPOSALS.save(deps.storage, key_proposal_id, &proposal)?;
+            proposal_ids.push(proposal_id);
+        }
+
+        let event = Event::new("create_round")
+            .add_attribute("round_id", round_id)
+            .add_attribute("budget", budget.amount)
+            .add_attribute("proposals", join_ids(&proposal_ids));
+        let round = Round {
+            proposals: proposal_ids,
+            budget: budget.amount,
+            algorithm,
+            milestones_required,
+            vesting,
+            retro,
+        };
+        ROUNDS.save(deps.storage, key_round_id, &round)?;
+
+        Ok(Response::new()
+            .add_event(event)
+            .add_attribute("action", "create_round")
+            .add_attribute("round_id", round_id)
+            .add_attribute("budget", budget.amount))
+    }
+}
+
+// sybil resistance, checked before a vote, commitment or delegation is accepted
+fn is_eligible(deps: Deps, state: &State, address: &Addr) -> StdResult<bool> {
+    match &state.voter_eligibility {
+        VoterEligibility::Open => Ok(true),
+        VoterEligibility::Allowlist => Ok(ALLOWLIST.has(deps.storage, address.as_str().as_bytes())),
+        VoterEligibility::Registry { contract } => {
+            let res: IsVerifiedResponse = deps.querier.query_wasm_smart(
+                contract,
+                &RegistryQueryMsg::IsVerified {
+                    address: address.to_string(),
+                },
+            )?;
+            Ok(res.verified)
+        }
+    }
+}
+
+// conviction needed to pay `requested` out of `pool`, growing without bound
+// as the request approaches the whole pool
+fn required_conviction(threshold: Decimal, requested: Uint128, pool: Uint128) -> Option<Uint128> {
+    if requested >= pool {
+        return None;
+    }
+    Some((requested * threshold).multiply_ratio(pool, pool - requested))
+}
+
+// an explicit trust weight wins, then identities checked by the allowlist or the
+// registry count fully, anyone else gets the configured default
+fn trust_weight(deps: Deps, state: &State, address: &Addr) -> StdResult<Decimal> {
+    if let Some(weight) = TRUST_WEIGHTS.may_load(deps.storage, address.as_str().as_bytes())? {
+        return Ok(weight);
+    }
+    let verified = match &state.voter_eligibility {
+        VoterEligibility::Open => ALLOWLIST.has(deps.storage, address.as_str().as_bytes()),
+        _ => is_eligible(deps, state, address)?,
+    };
+    Ok(match verified {
+        true => Decimal::one(),
+        false => state.default_trust_weight,
+    })
+}
+
+// collects the per-voter contributions of every round proposal that is still
+// competing for the matching pool
+fn round_raw_grants(deps: Deps, round: &Round) -> StdResult<Vec<(Uint128, RawGrant)>> {
+    Ok(round_contributions(deps, round)?
+        .into_iter()
+        .map(|(id, grant, _)| (id, grant))
+        .collect())
+}
+
+// same as round_raw_grants, with the contributor behind each entry of funds
+fn round_contributions(
+    deps: Deps,
+    round: &Round,
+) -> StdResult<Vec<(Uint128, RawGrant, Vec<Addr>)>> {
+    let state = STATE.load(deps.storage)?;
+    let mut grants = vec![];
+    for proposal_id in &round.proposals {
+        let proposal = PROPOSALS.load(deps.storage, &proposal_id.to_be_bytes())?;
+        if proposal.status == ProposalStatus::Rejected {
+            continue;
+        }
+        let mut funds = vec![];
+        let mut weights = vec![];
+        let mut negative_funds = vec![];
+        let mut negative_weights = vec![];
+        let mut contributors = vec![];
+        for voter in &proposal.voters {
+            let balance = BALANCES
+                .may_load(deps.storage, voter.as_str().as_bytes())?
+                .unwrap_or_default();
+            if let Some((_, amount)) = balance
+                .locked_tokens
+                .iter()
+                .find(|(id, _)| id == proposal_id)
+            {
+                let weight = trust_weight(deps, &state, voter)?;
+                let against = match round.algorithm.negative_votes() {
+                    true => balance
+                        .locked_against
+                        .iter()
+                        .find(|(id, _)| id == proposal_id)
+                        .map(|(_, against)| *against)
+                        .unwrap_or_default(),
+                    false => Uint128::zero(),
+                };
+                funds.push((*amount - against).u128());
+                weights.push(weight);
+                contributors.push(voter.clone());
+                if !against.is_zero() {
+                    negative_funds.push(against.u128());
+                    negative_weights.push(weight);
+                }
+            }
+        }
+        grants.push((
+            *proposal_id,
+            RawGrant {
+                addr: proposal.fund_address,
+                collected_vote_funds: funds.iter().sum(),
+                funds,
+                weights,
+                negative_funds,
+                negative_weights,
+            },
+            contributors,
+        ));
+    }
+    Ok(grants)
+}
+
+fn estimate_round(
+    deps: Deps,
+    round_id: Uint128,
+    round: &Round,
+) -> StdResult<(Vec<(Uint128, CalculatedGrant)>, Uint128)> {
+    match &round.retro {
+        Some(retro) => allocate_retro_round(deps, round_id, round, retro),
+        None => calculate_round(round, round_raw_grants(deps, round)?),
+    }
+}
+
+// aggregates the badgeholders' allocations, proposals left out of a ballot get 0 from it
+fn allocate_retro_round(
+    deps: Deps,
+    round_id: Uint128,
+    round: &Round,
+    retro: &RetroFunding,
+) -> StdResult<(Vec<(Uint128, CalculatedGrant)>, Uint128)> {
+    let mut projects = vec![];
+    for proposal_id in &round.proposals {
+        let proposal = PROPOSALS.load(deps.storage, &proposal_id.to_be_bytes())?;
+        if proposal.status != ProposalStatus::Rejected {
+            projects.push((*proposal_id, proposal.fund_address));
+        }
+    }
+    let ballots = ALLOCATIONS
+        .prefix(round_id.to_be_bytes().as_slice())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (_, allocations) = item?;
+            Ok(projects
+                .iter()
+                .map(|(id, _)| {
+                    allocations
+                        .iter()
+                        .find(|(allocated, _)| allocated == id)
+                        .map(|(_, amount)| amount.u128())
+                        .unwrap_or_default()
+                })
+                .collect())
+        })
+        .collect::<StdResult<Vec<Vec<u128>>>>()?;
+
+    let (payouts, leftover) =
+        retro
+            .aggregation
+            .allocate(projects.len(), &ballots, round.budget.u128());
+    let grants = projects
+        .into_iter()
+        .zip(payouts)
+        .map(|((proposal_id, addr), grant)| {
+            (
+                proposal_id,
+                CalculatedGrant {
+                    addr,
+                    grant,
+                    collected_vote_funds: 0,
+                    ineligible_reasons: vec![],
+                },
+            )
+        })
+        .collect();
+    Ok((grants, Uint128::from(leftover)))
+}
+
+fn calculate_round(
+    round: &Round,
+    raw_grants: Vec<(Uint128, RawGrant)>,
+) -> StdResult<(Vec<(Uint128, CalculatedGrant)>, Uint128)> {
+    let (proposal_ids, raw_grants): (Vec<_>, Vec<_>) = raw_grants.into_iter().unzip();
+    // tokens paid for no-votes go to the round leftover rather than the project,
+    // governance withdraws them with the rest of it, see withdraw_leftover
+    let against: u128 = raw_grants
+        .iter()
+        .map(|g| g.negative_funds.iter().sum::<u128>())
+        .sum();
+    let (grants, leftover) = round
+        .algorithm
+        .calculate(raw_grants, Some(round.budget.u128()))
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    Ok((
+        proposal_ids.into_iter().zip(grants).collect(),
+        Uint128::from(leftover + against),
+    ))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&STATE.load(deps.storage)?),
+        QueryMsg::TokenStake { address } => {
+            query::token_balance(deps, deps.api.addr_validate(address.as_str())?)
+        }
+        QueryMsg::GetProposal { proposal_id } => {
+            to_json_binary(&query::get_proposal(deps, proposal_id)?)
+        }
+        QueryMsg::EstimatedMatches { round_id } => {
+            to_json_binary(&query::estimated_matches(deps, round_id)?)
+        }
+        QueryMsg::EstimatedMatch { proposal_id } => {
+            to_json_binary(&query::estimated_match(deps, proposal_id)?)
+        }
+        QueryMsg::Distribution { round_id } => {
+            to_json_binary(&DISTRIBUTIONS.load(deps.storage, &round_id.to_be_bytes())?)
+        }
+        QueryMsg::Escrow { proposal_id } => {
+            to_json_binary(&ESCROWS.load(deps.storage, &proposal_id.to_be_bytes())?)
+        }
+        QueryMsg::Stream { proposal_id } => {
+            to_json_binary(&query::stream(deps, env, proposal_id)?)
+        }
+        QueryMsg::Delegation { address } => to_json_binary(&query::delegation(deps, address)?),
+        QueryMsg::RoundResults { round_id } => {
+            to_json_binary(&query::round_results(deps, round_id)?)
+        }
+        QueryMsg::Conviction { proposal_id } => {
+            to_json_binary(&query::conviction(deps, env, proposal_id)?)
+        }
+        QueryMsg::Proposal { proposal_id } => {
+            to_json_binary(&query::cw3_proposal(deps, env, proposal_id)?)
+        }
+        QueryMsg::ListProposals { start_after, limit } => {
+            to_json_binary(&query::list_proposals(deps, env, start_after, limit)?)
+        }
+        QueryMsg::Vote { proposal_id, voter } => to_json_binary(&VoteResponse {
+            vote: query::vote_info(deps, proposal_id, &voter)?,
+        }),
+        QueryMsg::ListVotes {
+            proposal_id,
+            start_after,
+            limit,
+        } => to_json_binary(&query::list_votes(deps, proposal_id, start_after, limit)?),
+        QueryMsg::Threshold {} => to_json_binary(&majority_threshold()),
+        QueryMsg::Treasury {} => to_json_binary(&TreasuryResponse {
+            fee: STATE.load(deps.storage)?.fee,
+            balance: TREASURY.may_load(deps.storage)?.unwrap_or_default(),
+        }),
+        QueryMsg::Eligible { address } => {
+            let state = STATE.load(deps.storage)?;
+            to_json_binary(&EligibleResponse {
+                eligible: is_eligible(deps, &state, &address)?,
+            })
+        }
+        QueryMsg::SimulateContribution {
+            proposal_id,
+            amount,
+            contributor,
+        } => to_json_binary(&query::simulate_contribution(
+            deps,
+            proposal_id,
+            amount,
+            contributor,
+        )?),
+    }
+}
+
+// proposals pass with more yes than no votes, open voting has no fixed total weight
+fn majority_threshold() -> ThresholdResponse {
+    ThresholdResponse::ThresholdQuorum {
+        threshold: Decimal::percent(50),
+        quorum: Decimal::zero(),
+        total_weight: 0,
+    }
+}
+
+pub mod query {
+    use super::*;
+
+    const DEFAULT_LIMIT: u32 = 10;
+    const MAX_LIMIT: u32 = 30;
+
+    pub fn cw3_proposal(deps: Deps, env: Env, proposal_id: u64) -> StdResult<ProposalResponse> {
+        let key = Uint128::from(proposal_id).to_be_bytes();
+        let proposal = PROPOSALS.load(deps.storage, &key)?;
+        map_proposal(deps, &env, proposal_id, proposal)
+    }
+
+    fn map_proposal(
+        deps: Deps,
+        env: &Env,
+        id: u64,
+        proposal: Proposal,
+    ) -> StdResult<ProposalResponse> {
+        let key = Uint128::from(id).to_be_bytes();
+        let msgs = match PARAMETER_CHANGES.may_load(deps.storage, &key)? {
+            Some(change) => execute::parameter_change_msgs(env, change)?,
+            None => PROPOSAL_MSGS
+                .may_load(deps.storage, &key)?
+                .unwrap_or_default(),
+        };
+        let status = match proposal.status {
+            ProposalStatus::PreQual => Cw3Status::Pending,
+            ProposalStatus::InProgress | ProposalStatus::Revealing | ProposalStatus::Reviewed => {
+                Cw3Status::Open
+            }
+            ProposalStatus::Rejected => Cw3Status::Rejected,
+            ProposalStatus::Passed => Cw3Status::Passed,
+            ProposalStatus::Executed => Cw3Status::Executed,
+        };
+        // only the reveal phase has a deadline, the owner closes the other phases
+        let expires = match proposal.reveal_ends {
+            Some(reveal_ends) if proposal.status == ProposalStatus::Revealing => {
+                Expiration::AtTime(reveal_ends)
+            }
+            _ => Expiration::Never {},
+        };
+        Ok(ProposalResponse {
+            id,
+            title: format!("Proposal {}", id),
+            description: proposal.fund_address,
+            msgs,
+            status,
+            expires,
+            threshold: majority_threshold(),
+        })
+    }
+
+    pub fn list_proposals(
+        deps: Deps,
+        env: Env,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<ProposalListResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start =
+            start_after.map(|id| Bound::ExclusiveRaw(Uint128::from(id).to_be_bytes().to_vec()));
+        let mut proposals = vec![];
+        for item in PROPOSALS.range(deps.storage, start, None, Order::Ascending) {
+            if proposals.len() == limit {
+                break;
+            }
+            let (key, proposal) = item?;
+            let key: [u8; 16] = key
+                .try_into()
+                .map_err(|_| StdError::generic_err("Invalid proposal key"))?;
+            // ids are ascending, the ones past u64 have no cw3 id
+            let id = match u64::try_from(u128::from_be_bytes(key)) {
+                Ok(id) => id,
+                Err(_) => break,
+            };
+            proposals.push(map_proposal(deps, &env, id, proposal)?);
+        }
+        Ok(ProposalListResponse { proposals })
+    }
+
+    // mixed yes and no votes report the side with more votes
+    pub fn vote_info(deps: Deps, proposal_id: u64, voter: &str) -> StdResult<Option<VoteInfo>> {
+        let id = Uint128::from(proposal_id);
+        let voter_state = match VOTERS.may_load(deps.storage, voter.as_bytes())? {
+            Some(voter_state) => voter_state,
+            None => return Ok(None),
+        };
+        let count = match voter_state.vote_count.iter().find(|(p, _)| *p == id) {
+            Some((_, count)) => *count,
+            None => return Ok(None),
+        };
+        let against = voter_state
+            .against
+            .iter()
+            .find(|(p, _)| *p == id)
+            .map(|(_, against)| *against)
+            .unwrap_or_default();
+        Ok(Some(VoteInfo {
+            proposal_id,
+            voter: voter.to_string(),
+            vote: if against * 2 > count {
+                Cw3Vote::No
+            } else {
+                Cw3Vote::Yes
+            },
+            weight: count,
+        }))
+    }
+
+    pub fn list_votes(
+        deps: Deps,
+        proposal_id: u64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<VoteListResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let proposal = PROPOSALS.load(deps.storage, &Uint128::from(proposal_id).to_be_bytes())?;
+        let mut voters: Vec<String> = proposal.voters.into_iter().map(String::from).collect();
+        voters.sort();
+        voters.dedup();
+        let mut votes = vec![];
+        for voter in voters
+            .iter()
+            .filter(|v| start_after.iter().all(|start| *v > start))
+        {
+            if votes.len() == limit {
+                break;
+            }
+            if let Some(vote) = vote_info(deps, proposal_id, voter)? {
+                votes.push(vote);
+            }
+        }
+        Ok(VoteListResponse { votes })
+    }
+
+    pub fn get_proposal(deps: Deps, proposal_id: Uint128) -> StdResult<GetProposalResponse> {
+        let key = &proposal_id.to_be_bytes();
+        let proposal = PROPOSALS.load(deps.storage, key)?;
+        Ok(GetProposalResponse {
+            status: proposal.status,
+            yes_votes: proposal.yes_votes,
+            no_votes: proposal.no_votes,
+        })
+    }
+
+    pub fn token_balance(deps: Deps, address: Addr) -> StdResult<Binary> {
+        let token_manager = BALANCES
+            .may_load(deps.storage, address.as_str().as_bytes())?
+            .unwrap_or_default();
+
+        let resp = TokenStakeResponse {
+            token_balance: token_manager.token_balance,
+        };
+
+        to_json_binary(&resp)
+    }
+
+    pub fn estimated_matches(deps: Deps, round_id: Uint128) -> StdResult<EstimatedMatchesResponse> {
+        let state = STATE.load(deps.storage)?;
+        let round = ROUNDS.load(deps.storage, &round_id.to_be_bytes())?;
+        let (mut grants, leftover) = estimate_round(deps, round_id, &round)?;
+        let fees = deduct_fees(&state, &mut grants);
+        Ok(EstimatedMatchesResponse {
+            grants: grants.into_iter().map(|(_, g)| g).collect(),
+            fees,
+            leftover,
+        })
+    }
+
+    pub fn estimated_match(deps: Deps, proposal_id: Uint128) -> StdResult<EstimatedMatchResponse> {
+        let proposal = PROPOSALS.load(deps.storage, &proposal_id.to_be_bytes())?;
+        let round_id = proposal
+            .round_id
+            .ok_or_else(|| StdError::generic_err("Proposal is not part of a round"))?;
+        let state = STATE.load(deps.storage)?;
+        let round = ROUNDS.load(deps.storage, &round_id.to_be_bytes())?;
+        let (mut grants, _) = estimate_round(deps, round_id, &round)?;
+        let fees = deduct_fees(&state, &mut grants);
+        let (grant, fee) = grants
+            .into_iter()
+            .zip(fees)
+            .find(|((id, _), _)| *id == proposal_id)
+            .map(|((_, g), fee)| (g, fee))
+            .ok_or_else(|| StdError::generic_err("Proposal is not competing for matching"))?;
+        Ok(EstimatedMatchResponse {
+            round_id,
+            grant,
+            fee,
+        })
+    }
+
+    pub fn round_results(deps: Deps, round_id: Uint128) -> StdResult<RoundResultsResponse> {
+        let key_round_id = &round_id.to_be_bytes();
+        let state = STATE.load(deps.storage)?;
+        let ranking = state
+            .ranking
+            .ok_or_else(|| StdError::generic_err("Ranked and approval ballots are not enabled"))?;
+        let round = ROUNDS.load(deps.storage, key_round_id)?;
+
+        let mut candidates = vec![];
+        for proposal_id in round.proposals {
+            let proposal = PROPOSALS.load(deps.storage, &proposal_id.to_be_bytes())?;
+            if proposal.status != ProposalStatus::Rejected {
+                candidates.push(proposal_id);
+            }
+        }
+        // ballots of the other type were cast under a previous method
+        let ballots: Vec<Vec<Uint128>> = BALLOTS
+            .prefix(key_round_id.as_slice())
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter_map(|item| match item {
+                Ok((_, Ballot::Approval { approved })) => {
+                    (ranking.method == RankingMethod::Approval).then_some(Ok(approved))
+                }
+                Ok((_, Ballot::Ranked { ranking: ranked })) => {
+                    (ranking.method != RankingMethod::Approval).then_some(Ok(ranked))
+                }
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<StdResult<_>>()?;
+
+        let result = ranking
+            .method
+            .tally(&candidates, &ballots, ranking.winners as usize);
+        Ok(RoundResultsResponse {
+            method: ranking.method,
+            winners: result.winners,
+            scores: result.scores,
+            ballots: ballots.len() as u64,
+        })
+    }
+
+    pub fn conviction(deps: Deps, env: Env, proposal_id: Uint128) -> StdResult<ConvictionResponse> {
+        let state = STATE.load(deps.storage)?;
+        let mut conviction = CONVICTIONS.load(deps.storage, &proposal_id.to_be_bytes())?;
+        let pool = CONVICTION_POOL.may_load(deps.storage)?.unwrap_or_default();
+
+        let mut required = None;
+        if let VotingMode::Conviction { decay, threshold } = state.voting_mode {
+            conviction.accrue(env.block.height, decay);
+            required = required_conviction(threshold, conviction.requested, pool);
+        }
+        Ok(ConvictionResponse {
+            requested: conviction.requested,
+            staked: conviction.staked,
+            conviction: conviction.conviction,
+            required,
+            pool,
+        })
+    }
+
+    pub fn stream(deps: Deps, env: Env, proposal_id: Uint128) -> StdResult<StreamResponse> {
+        let stream = STREAMS.load(deps.storage, &proposal_id.to_be_bytes())?;
+        Ok(StreamResponse {
+            vested: stream.vested(env.block.time),
+            stream,
+        })
+    }
+
+    pub fn delegation(deps: Deps, address: Addr) -> StdResult<DelegationResponse> {
+        let key_address = address.as_str().as_bytes();
+        Ok(DelegationResponse {
+            delegate: DELEGATIONS.may_load(deps.storage, key_address)?,
+            delegators: DELEGATORS
+                .may_load(deps.storage, key_address)?
+                .unwrap_or_default(),
+            credits: DELEGATION_CREDITS
+                .may_load(deps.storage, key_address)?
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn simulate_contribution(
+        deps: Deps,
+        proposal_id: Uint128,
+        amount: Uint128,
+        contributor: Option<Addr>,
+    ) -> StdResult<SimulateContributionResponse> {
+        let proposal = PROPOSALS.load(deps.storage, &proposal_id.to_be_bytes())?;
+        let round_id = proposal
+            .round_id
+            .ok_or_else(|| StdError::generic_err("Proposal is not part of a round"))?;
+        let round = ROUNDS.load(deps.storage, &round_id.to_be_bytes())?;
+        if round.retro.is_some() {
+            return Err(StdError::generic_err("Retro rounds are not matched"));
+        }
+
+        let state = STATE.load(deps.storage)?;
+        let contributions = round_contributions(deps, &round)?;
+        // matches as paid out, after the platform fee
+        let match_of = |mut grants: Vec<(Uint128, CalculatedGrant)>| {
+            deduct_fees(&state, &mut grants);
+            grants
+                .into_iter()
+                .find(|(id, _)| *id == proposal_id)
+                .map(|(_, g)| Uint128::from(g.grant))
+                .ok_or_else(|| StdError::generic_err("Proposal is not competing for matching"))
+        };
+        let raw_grants = contributions
+            .iter()
+            .map(|(id, grant, _)| (*id, grant.clone()))
+            .collect();
+        let (current, _) = calculate_round(&round, raw_grants)?;
+        let current_match = match_of(current)?;
+
+        // an existing contributor tops up their own square root term,
+        // anyone else adds a new one, with what is left of the vote after the fee
+        let amount = amount - platform_fee(&state, amount);
+        let weight = match &contributor {
+            Some(contributor) => trust_weight(deps, &state, contributor)?,
+            None => state.default_trust_weight,
+        };
+        let simulated_grants = contributions
+            .into_iter()
+            .map(|(id, mut grant, contributors)| {
+                if id == proposal_id {
+                    let mut topped_up = amount.u128();
+                    let existing = contributor
+                        .as_ref()
+                        .and_then(|c| contributors.iter().position(|a| a == c));
+                    if let Some(index) = existing {
+                        topped_up += grant.funds.remove(index);
+                        grant.weights.remove(index);
+                    }
+                    grant.funds.push(topped_up);
+                    grant.weights.push(weight);
+                    grant.collected_vote_funds += amount.u128();
+                }
+                (id, grant)
+            })
+            .collect();
+        let (simulated, _) = calculate_round(&round, simulated_grants)?;
+        let simulated_match = match_of(simulated)?;
+        let signed =
+            |m: Uint128| Int128::try_from(m).map_err(|e| StdError::generic_err(e.to_string()));
+
+        Ok(SimulateContributionResponse {
+            current_match,
+            simulated_match,
+            match_change: signed(simulated_match)?.checked_sub(signed(current_match)?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quadratic::{MatchingCap, QuadraticFundingAlgorithm};
+    use crate::retro::Aggregation;
+    use crate::state::LegacyBalanceVote;
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{coins, from_json, Coin, CosmosMsg, OwnedDeps};
+    const MIN_STAKE_AMOUNT: u128 = 1000;
+    const TEST_VOTER: &str = "voter1";
+    const TEST_VOTER_2: &str = "voter2";
+    const TEST_VOTER_3: &str = "voter3";
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let info = mock_info("creator", &coins(1000, "earth"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+    }
+
+    #[test]
+    fn vote() {
+        const BASE: u128 = 1000;
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let info = mock_info("creator", &coins(2 * BASE, &msg.denom));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+
+        let info = mock_info(TEST_VOTER, &coins(BASE, &msg.denom));
+        let yes_vote = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(1u128), value.yes_votes);
+        assert_eq!(Uint128::from(0u128), value.no_votes);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER.to_string()),
+            },
+        )
+        .unwrap();
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+
+        assert_eq!(Uint128::from(BASE), token_stake.token_balance);
+
+        let info = mock_info(TEST_VOTER_2, &coins(BASE, &msg.denom));
+
+        let yes_vote = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(2u128), value.yes_votes);
+        assert_eq!(Uint128::from(0u128), value.no_votes);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER_2.to_string()),
+            },
+        )
+        .unwrap();
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+
+        assert_eq!(Uint128::from(BASE), token_stake.token_balance);
+        
+        //Third time
+
+        let yes_vote = false;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+
+        let info = mock_info(TEST_VOTER_2, &coins(4 * BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(2u128), value.yes_votes);
+        assert_eq!(Uint128::from(1u128), value.no_votes);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER_2.to_string()),
+            },
+        )
+        .unwrap();
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+
+        assert_eq!(Uint128::from(5 * BASE), token_stake.token_balance);
+
+        //Four times
+        let yes_vote = false;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+
+        let info = mock_info(TEST_VOTER_2, &coins(9 * BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(2u128), value.yes_votes);
+        assert_eq!(Uint128::from(2u128), value.no_votes);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER_2.to_string()),
+            },
+        )
+        .unwrap();
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+
+        assert_eq!(Uint128::from(14 * BASE), token_stake.token_balance);
+
+        // Vote another proposal id
+        let yes_vote = false;
+        let proposal_id = Uint128::from(2u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+
+        let info = mock_info(TEST_VOTER_3, &coins(BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(2u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(0u128), value.yes_votes);
+        assert_eq!(Uint128::from(1u128), value.no_votes);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER_3.to_string()),
+            },
+        )
+        .unwrap();
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+
+        assert_eq!(Uint128::from(BASE), token_stake.token_balance);
+
+        //Voter 3 vote for proposal 2
+        let yes_vote = true;
+        let proposal_id = Uint128::from(2u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+
+        let info = mock_info(TEST_VOTER_3, &coins(4 * BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(2u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(1u128), value.yes_votes);
+        assert_eq!(Uint128::from(1u128), value.no_votes);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER_3.to_string()),
+            },
+        )
+        .unwrap();
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+
+        assert_eq!(Uint128::from(5 * BASE), token_stake.token_balance);
+    }
+
+    #[test]
+    fn end_vote_passed() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &coins(BASE, &msg.denom));
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+
+        let voter_info = mock_info("anyone", &coins(BASE, &msg.denom));
+
+        let yes_vote = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
+
+        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_vote).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+
+        assert_eq!(value.status, ProposalStatus::Reviewed);
+    }
+
+    #[test]
+    fn end_vote_rejected() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &coins(BASE, &msg.denom));
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+
+        let voter_info = mock_info("anyone", &coins(BASE, &msg.denom));
+
+        let yes_vote = false;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
+
+        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_vote).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+
+        assert_eq!(value.status, ProposalStatus::Rejected);
+    }
+
+
+
+    #[test]
+    fn review() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &coins(BASE, &msg.denom));
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+
+        let voter_info = mock_info("anyone", &coins(BASE, &msg.denom));
+
+        let yes_vote = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
+
+        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_vote).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+
+        assert_eq!(value.status, ProposalStatus::Reviewed);
+
+
+        let approved = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Review {
+            proposal_id,
+            approved,
+        };
+
+        let info = mock_info("reviewer1", &coins(BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(1u128), value.yes_votes);
+        assert_eq!(Uint128::from(0u128), value.no_votes);
+        
+
+        let approved = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Review {
+            proposal_id,
+            approved,
+        };
+
+        let info = mock_info("reviewer2", &coins(BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(2u128), value.yes_votes);
+        assert_eq!(Uint128::from(0u128), value.no_votes);
+
+    }
+
+
+    #[test]
+    fn end_review_passed() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &coins(BASE, &msg.denom));
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+
+        let voter_info = mock_info("anyone", &coins(BASE, &msg.denom));
+
+        let yes_vote = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
+
+        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_end_vote).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+
+        assert_eq!(value.status, ProposalStatus::Reviewed);
+
+
+        let approved = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Review {
+            proposal_id,
+            approved,
+        };
+
+        let info = mock_info("reviewer1", &coins(BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg_execute.clone()).unwrap();
+
+        // a second review by the same reviewer costs 2^2 units
+        let err = execute(deps.as_mut(), mock_env(), info, msg_execute.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFundsSent {}));
+        let info = mock_info("reviewer1", &coins(4 * BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+
+        let msg_end_review = ExecuteMsg::EndReview { proposal_id };
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_review).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+
+        assert_eq!(value.status, ProposalStatus::Passed);
+
+
+    }
+
+    #[test]
+    fn end_review_rejected() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &coins(BASE, &msg.denom));
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+
+        let voter_info = mock_info("anyone", &coins(BASE, &msg.denom));
+
+        let yes_vote = true;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
+
+        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_end_vote).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+
+        assert_eq!(value.status, ProposalStatus::Reviewed);
+
+
+        let approved = false;
+        let proposal_id = Uint128::from(1u128);
+        let msg_execute = ExecuteMsg::Review {
+            proposal_id,
+            approved,
+        };
+
+        let info = mock_info("reviewer1", &coins(BASE, &msg.denom));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+
+
+        let msg_end_review = ExecuteMsg::EndReview { proposal_id };
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_review).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+
+        assert_eq!(value.status, ProposalStatus::Rejected);
+
+
+    }
+
+    fn create_round(deps: DepsMut, owner: &str, budget: u128) {
+        let msg = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![
+                (Uint128::from(1u128), "project1".to_string()),
+                (Uint128::from(2u128), "project2".to_string()),
+            ],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: None,
+            retro: None,
+        };
+        let info = mock_info(owner, &coins(budget, VOTING_TOKEN));
+        execute(deps, mock_env(), info, msg).unwrap();
+    }
+
+    #[test]
+    fn estimated_matches() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info, msg.clone()).unwrap();
+
+        // only the owner can open a round
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: None,
+            retro: None,
+        };
+        let info = mock_info("anyone", &coins(10 * BASE, &msg.denom));
+        let err = execute(deps.as_mut(), mock_env(), info, msg_round).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        create_round(deps.as_mut(), "creator", 10 * BASE);
+
+        let votes = [
+            (TEST_VOTER, 1u128, BASE),
+            (TEST_VOTER_2, 1u128, BASE),
+            (TEST_VOTER_3, 2u128, BASE),
+            (TEST_VOTER_3, 2u128, 4 * BASE),
+        ];
+        for (voter, proposal_id, amount) in votes {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            };
+            let info = mock_info(voter, &coins(amount, &msg.denom));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EstimatedMatches {
+                round_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: EstimatedMatchesResponse = from_json(&res).unwrap();
+        // (31 + 31)^2 = 3844 against 70^2 = 4900
+        assert_eq!(
+            value.grants,
+            vec![
+                CalculatedGrant {
+                    addr: "project1".to_string(),
+                    grant: 4396,
+                    collected_vote_funds: 2 * BASE,
+                    ineligible_reasons: vec![],
+                },
+                CalculatedGrant {
+                    addr: "project2".to_string(),
+                    grant: 5603,
+                    collected_vote_funds: 5 * BASE,
+                    ineligible_reasons: vec![],
+                },
+            ]
+        );
+        assert_eq!(value.leftover, Uint128::from(1u128));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EstimatedMatch {
+                proposal_id: Uint128::from(2u128),
+            },
+        )
+        .unwrap();
+        let value: EstimatedMatchResponse = from_json(&res).unwrap();
+        assert_eq!(value.round_id, Uint128::from(1u128));
+        assert_eq!(value.grant.grant, 5603);
+    }
+
+    #[test]
+    fn simulate_contribution() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+        create_round(deps.as_mut(), "creator", 10 * BASE);
+
+        let votes = [
+            (TEST_VOTER, 1u128, BASE),
+            (TEST_VOTER_2, 1u128, BASE),
+            (TEST_VOTER_3, 2u128, BASE),
+            (TEST_VOTER_3, 2u128, 4 * BASE),
+        ];
+        for (voter, proposal_id, amount) in votes {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            };
+            let info = mock_info(voter, &coins(amount, &msg.denom));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        }
+
+        // a third contributor: (31 + 31 + 31)^2 = 8649 against 4900
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateContribution {
+                proposal_id: Uint128::from(1u128),
+                amount: Uint128::from(BASE),
+                contributor: None,
+            },
+        )
+        .unwrap();
+        let value: SimulateContributionResponse = from_json(&res).unwrap();
+        assert_eq!(value.current_match, Uint128::from(4396u128));
+        assert_eq!(value.simulated_match, Uint128::from(6383u128));
+        assert_eq!(value.match_change, Int128::new(1987));
+
+        // topping up an existing contribution unlocks less: (44 + 31)^2 = 5625
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateContribution {
+                proposal_id: Uint128::from(1u128),
+                amount: Uint128::from(BASE),
+                contributor: Some(Addr::unchecked(TEST_VOTER)),
+            },
+        )
+        .unwrap();
+        let value: SimulateContributionResponse = from_json(&res).unwrap();
+        assert_eq!(value.simulated_match, Uint128::from(5344u128));
+        assert_eq!(value.match_change, Int128::new(948));
+
+        // nothing was written
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EstimatedMatch {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: EstimatedMatchResponse = from_json(&res).unwrap();
+        assert_eq!(value.grant.grant, 4396);
+
+        // with a platform fee both matches are net and the vote is counted after its fee
+        let msg_fee = ExecuteMsg::UpdateFee {
+            fee: Some(PlatformFee {
+                bps: 500,
+                treasury: Addr::unchecked("treasury"),
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_fee).unwrap();
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateContribution {
+                proposal_id: Uint128::from(1u128),
+                amount: Uint128::from(BASE),
+                contributor: None,
+            },
+        )
+        .unwrap();
+        let simulation: SimulateContributionResponse = from_json(&res).unwrap();
+        assert_eq!(simulation.current_match, Uint128::from(4396u128 - 219));
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info("voter4", &coins(BASE, &msg.denom));
+        execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EstimatedMatch {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: EstimatedMatchResponse = from_json(&res).unwrap();
+        assert_eq!(Uint128::from(value.grant.grant), simulation.simulated_match);
+    }
+
+    // takes a voted proposal through end_vote, one review and end_review
+    fn finish_review(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        proposal_id: u128,
+        approved: bool,
+    ) {
+        let reviewer = format!("reviewer{}", proposal_id);
+        let proposal_id = Uint128::from(proposal_id);
+        let owner_info = mock_info("creator", &[]);
+        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_end_vote).unwrap();
+        let info = mock_info(&reviewer, &coins(1000, VOTING_TOKEN));
+        let msg_review = ExecuteMsg::Review {
+            proposal_id,
+            approved,
+        };
+        execute(deps.as_mut(), mock_env(), info, msg_review).unwrap();
+        let msg_end_review = ExecuteMsg::EndReview { proposal_id };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_end_review).unwrap();
+    }
+
+    #[test]
+    fn funding_and_claim_grant() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+        create_round(deps.as_mut(), "creator", 10 * BASE);
+
+        let votes = [
+            (TEST_VOTER, 1u128, BASE),
+            (TEST_VOTER_2, 1u128, BASE),
+            (TEST_VOTER_3, 2u128, BASE),
+        ];
+        for (voter, proposal_id, amount) in votes {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            };
+            let info = mock_info(voter, &coins(amount, &msg.denom));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        }
+
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        let info = owner_info.clone();
+        let err = execute(deps.as_mut(), mock_env(), info, msg_funding.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::RoundNotFinalized { .. }));
+
+        // the whole pool goes to the only passed proposal
+        finish_review(&mut deps, 1, true);
+        finish_review(&mut deps, 2, false);
+        let info = owner_info.clone();
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_funding.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), owner_info, msg_funding).unwrap_err();
+        assert!(matches!(err, ContractError::RoundAlreadyDistributed {}));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Distribution {
+                round_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: Distribution = from_json(&res).unwrap();
+        assert_eq!(value.grants.len(), 1);
+        assert_eq!(value.grants[0].proposal_id, Uint128::from(1u128));
+        assert_eq!(value.grants[0].grant.grant, 10 * BASE);
+        assert!(!value.grants[0].claimed);
+        assert_eq!(value.leftover, Uint128::zero());
+
+        let msg_claim = ExecuteMsg::ClaimGrant {
+            round_id: Uint128::from(1u128),
+        };
+        let info = mock_info("project2", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg_claim.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+
+        let info = mock_info("project1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg_claim.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project1".to_string(),
+                amount: coins(12 * BASE, VOTING_TOKEN),
+            })
+        );
+        let err = execute(deps.as_mut(), mock_env(), info, msg_claim).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Distribution {
+                round_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: Distribution = from_json(&res).unwrap();
+        assert!(value.grants[0].claimed);
+    }
+
+    #[test]
+    fn milestone_escrow() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+
+        let msg_reviewers = ExecuteMsg::UpdateReviewers {
+            add: vec!["committee".to_string()],
+            remove: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_reviewers).unwrap();
+
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![(Uint128::from(1u128), "project1".to_string())],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: true,
+            vesting: None,
+            retro: None,
+        };
+        let info = mock_info("creator", &coins(10 * BASE, &msg.denom));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
+
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(BASE, &msg.denom));
+        execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        finish_review(&mut deps, 1, true);
+
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_funding).unwrap();
+
+        let project_info = mock_info("project1", &[]);
+        let msg_claim = ExecuteMsg::ClaimGrant {
+            round_id: Uint128::from(1u128),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            project_info.clone(),
+            msg_claim.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::MilestonesRequired { .. }));
+
+        let msg_milestones = ExecuteMsg::SetMilestones {
+            proposal_id: Uint128::from(1u128),
+            milestones: vec![Decimal::percent(50), Decimal::percent(40)],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            project_info.clone(),
+            msg_milestones,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMilestones {}));
+
+        let msg_milestones = ExecuteMsg::SetMilestones {
+            proposal_id: Uint128::from(1u128),
+            milestones: vec![
+                Decimal::percent(30),
+                Decimal::percent(30),
+                Decimal::percent(40),
+            ],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg_milestones.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            project_info.clone(),
+            msg_milestones,
+        )
+        .unwrap();
+
+        // the match and the collected funds go into escrow, nothing is sent yet
+        let res = execute(deps.as_mut(), mock_env(), project_info.clone(), msg_claim).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        let approve = |milestone: u32, approved: bool| ExecuteMsg::ApproveMilestone {
+            proposal_id: Uint128::from(1u128),
+            milestone,
+            approved,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            approve(0, true),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let committee_info = mock_info("committee", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            committee_info.clone(),
+            approve(0, true),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            committee_info.clone(),
+            approve(0, false),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::MilestoneNotPending {}));
+
+        let msg_claim_milestone = ExecuteMsg::ClaimMilestone {
+            proposal_id: Uint128::from(1u128),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            project_info.clone(),
+            msg_claim_milestone.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project1".to_string(),
+                amount: coins(3300, VOTING_TOKEN),
+            })
+        );
+
+        // a rejected tranche goes back to the round's pool
+        execute(deps.as_mut(), mock_env(), committee_info, approve(1, false)).unwrap();
+        let err =
+            execute(deps.as_mut(), mock_env(), project_info, msg_claim_milestone).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+
+        let msg_close = ExecuteMsg::CloseEscrow {
+            proposal_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_close).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Escrow {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: Escrow = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::from(11 * BASE));
+        let statuses: Vec<MilestoneStatus> =
+            value.milestones.into_iter().map(|m| m.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                MilestoneStatus::Claimed,
+                MilestoneStatus::Rejected,
+                MilestoneStatus::Rejected
+            ]
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Distribution {
+                round_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: Distribution = from_json(&res).unwrap();
+        assert_eq!(value.leftover, Uint128::from(3300u128 + 4400u128));
+    }
+
+    #[test]
+    fn vesting_stream() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+
+        let start = mock_env().block.time;
+        let at = |seconds: u64| {
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(seconds);
+            env
+        };
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![(Uint128::from(1u128), "project1".to_string())],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: Some(Vesting {
+                start,
+                end: start.plus_seconds(1000),
+            }),
+            retro: None,
+        };
+        let info = mock_info("creator", &coins(10 * BASE, &msg.denom));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
+
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(BASE, &msg.denom));
+        execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        finish_review(&mut deps, 1, true);
+
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_funding).unwrap();
+
+        let project_info = mock_info("project1", &[]);
+        let msg_claim = ExecuteMsg::ClaimGrant {
+            round_id: Uint128::from(1u128),
+        };
+        let res = execute(deps.as_mut(), mock_env(), project_info.clone(), msg_claim).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        let msg_claim_vested = ExecuteMsg::ClaimVested {
+            proposal_id: Uint128::from(1u128),
+        };
+        let res = execute(
+            deps.as_mut(),
+            at(250),
+            project_info.clone(),
+            msg_claim_vested.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project1".to_string(),
+                amount: coins(2750, VOTING_TOKEN),
+            })
+        );
+
+        let msg_query = QueryMsg::Stream {
+            proposal_id: Uint128::from(1u128),
+        };
+        let res = query(deps.as_ref(), at(500), msg_query.clone()).unwrap();
+        let value: StreamResponse = from_json(&res).unwrap();
+        assert_eq!(value.vested, Uint128::from(5500u128));
+        assert_eq!(value.stream.claimed, Uint128::from(2750u128));
+
+        let msg_claw_back = ExecuteMsg::ClawBack {
+            proposal_id: Uint128::from(1u128),
+        };
+        let err = execute(
+            deps.as_mut(),
+            at(500),
+            project_info.clone(),
+            msg_claw_back.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), at(500), owner_info, msg_claw_back).unwrap();
+
+        // only what vested before the claw back can still be claimed
+        let res = execute(
+            deps.as_mut(),
+            at(1000),
+            project_info.clone(),
+            msg_claim_vested.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project1".to_string(),
+                amount: coins(2750, VOTING_TOKEN),
+            })
+        );
+        let err = execute(deps.as_mut(), at(2000), project_info, msg_claim_vested).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Distribution {
+                round_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: Distribution = from_json(&res).unwrap();
+        assert_eq!(value.leftover, Uint128::from(5500u128));
+
+        // the clawed back funds are not stranded
+        let msg_withdraw = ExecuteMsg::WithdrawLeftover {
+            round_id: Uint128::from(1u128),
+            recipient: "creator".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            at(2000),
+            mock_info("creator", &[]),
+            msg_withdraw,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(5500, VOTING_TOKEN),
+            })
+        );
+    }
+
+    #[test]
+    fn delegated_voting() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info, msg.clone()).unwrap();
+
+        let msg_delegate = ExecuteMsg::Delegate {
+            to: "curator".to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("curator", &[]),
+            msg_delegate.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SelfDelegation {}));
+        // credits pay for the delegated quadratic votes, voter3 deposits none
+        let deposits = [
+            (TEST_VOTER, 3 * BASE),
+            (TEST_VOTER_2, 2 * BASE),
+            (TEST_VOTER_3, 0),
+        ];
+        for (delegator, deposit) in deposits {
+            let info = mock_info(delegator, &coins(deposit, VOTING_TOKEN));
+            execute(deps.as_mut(), mock_env(), info, msg_delegate.clone()).unwrap();
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Delegation {
+                address: Addr::unchecked("curator"),
+            },
+        )
+        .unwrap();
+        let value: DelegationResponse = from_json(&res).unwrap();
+        assert_eq!(value.delegate, None);
+        assert_eq!(
+            value.delegators,
+            vec![
+                Addr::unchecked(TEST_VOTER),
+                Addr::unchecked(TEST_VOTER_2),
+                Addr::unchecked(TEST_VOTER_3)
+            ]
+        );
+
+        let vote = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                    voter: &str,
+                    proposal_id: u128,
+                    yes_vote: bool,
+                    amount: u128| {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote,
+            };
+            let info = mock_info(voter, &coins(amount, VOTING_TOKEN));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        };
+        let tally = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, proposal_id: u128| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetProposal {
+                    proposal_id: Uint128::from(proposal_id),
+                },
+            )
+            .unwrap();
+            let value: GetProposalResponse = from_json(&res).unwrap();
+            (value.yes_votes.u128(), value.no_votes.u128())
+        };
+
+        // the curator carries the delegators with credits, once per proposal
+        vote(&mut deps, "curator", 1, true, BASE);
+        assert_eq!(tally(&deps, 1), (3, 0));
+        vote(&mut deps, "curator", 1, true, 4 * BASE);
+        assert_eq!(tally(&deps, 1), (4, 0));
+
+        // a delegator overrides the curator on this proposal
+        vote(&mut deps, TEST_VOTER_2, 1, false, BASE);
+        assert_eq!(tally(&deps, 1), (3, 1));
+
+        // the delegated vote is locked like the delegator's own, an override
+        // hands its stake back to the credits
+        let credits = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, address: &str| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Delegation {
+                    address: Addr::unchecked(address),
+                },
+            )
+            .unwrap();
+            let value: DelegationResponse = from_json(&res).unwrap();
+            value.credits.u128()
+        };
+        assert_eq!(credits(&deps, TEST_VOTER), 2 * BASE);
+        assert_eq!(credits(&deps, TEST_VOTER_2), 2 * BASE);
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER),
+            },
+        )
+        .unwrap();
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+        assert_eq!(token_stake.token_balance, Uint128::from(BASE));
+
+        vote(&mut deps, "curator", 2, true, BASE);
+        assert_eq!(tally(&deps, 2), (3, 0));
+
+        let info = mock_info(TEST_VOTER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Undelegate {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER.to_string(),
+                amount: coins(BASE, VOTING_TOKEN),
+            })
+        );
+        vote(&mut deps, "curator", 3, false, BASE);
+        assert_eq!(tally(&deps, 3), (0, 2));
+        vote(&mut deps, "curator", 4, true, BASE);
+        assert_eq!(tally(&deps, 4), (1, 0));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Delegation {
+                address: Addr::unchecked(TEST_VOTER_2),
+            },
+        )
+        .unwrap();
+        let value: DelegationResponse = from_json(&res).unwrap();
+        assert_eq!(value.delegate, Some(Addr::unchecked("curator")));
+    }
+
+    #[test]
+    fn commit_reveal_voting() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let msg_vote = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(BASE, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info.clone(), msg_vote.clone()).unwrap();
+
+        let msg_config = ExecuteMsg::UpdateConfig {
+            ballot_mode: Some(BallotMode::CommitReveal {
+                reveal_period: 100,
+                unrevealed: UnrevealedPolicy::Forfeit,
+            }),
+            voter_eligibility: None,
+            voting_mode: None,
+            ranking: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_config).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, msg_vote).unwrap_err();
+        assert!(matches!(err, ContractError::CommitRevealRequired {}));
+
+        let commitment = |voter: &str, votes: u64, yes: bool, salt: &str| {
+            let preimage = format!("{}:{}:{}:{}:{}", voter, 1, votes, yes, salt);
+            Binary::from(Sha256::digest(preimage.as_bytes()).to_vec())
+        };
+        let msg_commit = ExecuteMsg::CommitVote {
+            proposal_id: Uint128::from(1u128),
+            commitment: commitment(TEST_VOTER_2, 2, false, "salt"),
+        };
+        let info = mock_info(TEST_VOTER_2, &coins(10 * BASE, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info.clone(), msg_commit.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, msg_commit).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyCommitted {}));
+
+        // never revealed
+        let msg_commit = ExecuteMsg::CommitVote {
+            proposal_id: Uint128::from(1u128),
+            commitment: commitment(TEST_VOTER_3, 1, true, "salt"),
+        };
+        let info = mock_info(TEST_VOTER_3, &coins(2 * BASE, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_commit).unwrap();
+
+        let reveal = |salt: &str| ExecuteMsg::RevealVote {
+            proposal_id: Uint128::from(1u128),
+            votes: 2,
+            yes: false,
+            salt: salt.to_string(),
+        };
+        let info = mock_info(TEST_VOTER_2, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), reveal("salt")).unwrap_err();
+        assert!(matches!(err, ContractError::ProposalNotRevealing {}));
+
+        let msg_end = ExecuteMsg::EndVote {
+            proposal_id: Uint128::from(1u128),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            owner_info.clone(),
+            msg_end.clone(),
+        )
+        .unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), reveal("pepper")).unwrap_err();
+        assert!(matches!(err, ContractError::CommitmentMismatch {}));
+        // two votes cost 1 + 4 units, the rest of the deposit is refunded
+        let res = execute(deps.as_mut(), mock_env(), info, reveal("salt")).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER_2.to_string(),
+                amount: coins(5 * BASE, VOTING_TOKEN),
+            })
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner_info.clone(),
+            msg_end.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RevealPeriodNotOver {}));
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = execute(deps.as_mut(), env.clone(), owner_info, msg_end).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(2 * BASE, VOTING_TOKEN),
+            })
+        );
+
+        let res = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::GetProposal {
+                proposal_id: Uint128::from(1u128),
+            },
+        )
+        .unwrap();
+        let value: GetProposalResponse = from_json(&res).unwrap();
+        assert_eq!(value.status, ProposalStatus::Rejected);
+    }
+
+    #[test]
+    fn voter_allowlist() {
+        let mut deps = mock_dependencies();
+
+        const BASE: u128 = 1000;
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let msg_config = ExecuteMsg::UpdateConfig {
+            ballot_mode: None,
+            voter_eligibility: Some(VoterEligibility::Allowlist),
+            voting_mode: None,
+            ranking: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_config).unwrap();
+        let msg_allowlist = ExecuteMsg::UpdateAllowlist {
+            add: vec![TEST_VOTER.to_string()],
+            remove: vec![],
+        };
+        let info = mock_info(TEST_VOTER, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg_allowlist.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), owner_info, msg_allowlist).unwrap();
+
+        let msg_vote = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER_2, &coins(BASE, VOTING_TOKEN));
+        let err = execute(deps.as_mut(), mock_env(), info, msg_vote.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::VoterNotEligible {}));
+        let info = mock_info(TEST_VOTER, &coins(BASE, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_vote).unwrap();
+
+        let msg_delegate = ExecuteMsg::Delegate {
+            to: TEST_VOTER.to_string(),
+        };
+        let info = mock_info(TEST_VOTER_2, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg_delegate).unwrap_err();
+        assert!(matches!(err, ContractError::VoterNotEligible {}));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Eligible {
+                address: Addr::unchecked(TEST_VOTER),
+            },
+        )
+        .unwrap();
+        let value: EligibleResponse = from_json(&res).unwrap();
+        assert!(value.eligible);
+
+        // a delegator dropped from the allowlist is not counted anymore
+        let owner_info = mock_info("creator", &[]);
+        let msg_allowlist = ExecuteMsg::UpdateAllowlist {
+            add: vec![TEST_VOTER_3.to_string()],
+            remove: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_allowlist).unwrap();
+        let msg_delegate = ExecuteMsg::Delegate {
+            to: TEST_VOTER.to_string(),
+        };
+        let info = mock_info(TEST_VOTER_3, &coins(2 * BASE, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_delegate).unwrap();
+        let yes_votes = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                         proposal_id: u128| {
+            let msg_vote = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            };
+            let info = mock_info(TEST_VOTER, &coins(BASE, VOTING_TOKEN));
+            execute(deps.as_mut(), mock_env(), info, msg_vote).unwrap();
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetProposal {
+                    proposal_id: Uint128::from(proposal_id),
+                },
+            )
+            .unwrap();
+            let value: GetProposalResponse = from_json(&res).unwrap();
+            value.yes_votes.u128()
         };
+        assert_eq!(yes_votes(&mut deps, 2), 2);
 
-        to_binary(&resp)
+        let msg_allowlist = ExecuteMsg::UpdateAllowlist {
+            add: vec![],
+            remove: vec![TEST_VOTER_3.to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_allowlist).unwrap();
+        assert_eq!(yes_votes(&mut deps, 3), 1);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary};
-    const TEST_VOTER: &str = "voter1";
-    const TEST_VOTER_2: &str = "voter2";
-    const TEST_VOTER_3: &str = "voter3";
 
     #[test]
-    fn proper_initialization() {
+    fn voting_modes() {
         let mut deps = mock_dependencies();
 
         let msg = InstantiateMsg {
             denom: String::from(VOTING_TOKEN),
         };
-        let info = mock_info("creator", &coins(1000, "earth"));
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let set_mode = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, mode| {
+            let msg_config = ExecuteMsg::UpdateConfig {
+                ballot_mode: None,
+                voter_eligibility: None,
+                voting_mode: Some(mode),
+                ranking: None,
+            };
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                msg_config,
+            )
+            .unwrap();
+        };
+        let vote = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                    voter: &str,
+                    proposal_id: u128,
+                    yes_vote: bool,
+                    amount: u128| {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote,
+            };
+            let funds = if amount > 0 {
+                coins(amount, VOTING_TOKEN)
+            } else {
+                vec![]
+            };
+            let info = mock_info(voter, &funds);
+            execute(deps.as_mut(), mock_env(), info, msg_execute)
+        };
+        let tally = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, proposal_id: u128| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetProposal {
+                    proposal_id: Uint128::from(proposal_id),
+                },
+            )
+            .unwrap();
+            let value: GetProposalResponse = from_json(&res).unwrap();
+            (value.yes_votes.u128(), value.no_votes.u128())
+        };
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+        // the tally follows the stake
+        set_mode(&mut deps, VotingMode::Linear);
+        vote(&mut deps, TEST_VOTER, 1, true, 2500).unwrap();
+        vote(&mut deps, TEST_VOTER, 1, true, 500).unwrap();
+        vote(&mut deps, TEST_VOTER_2, 1, false, 700).unwrap();
+        assert_eq!(tally(&deps, 1), (3000, 700));
+        let err = vote(&mut deps, TEST_VOTER_2, 1, false, 0).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFundsSent {}));
+
+        // one free vote for each allowlisted voter
+        set_mode(&mut deps, VotingMode::OnePersonOneVote);
+        let msg_allowlist = ExecuteMsg::UpdateAllowlist {
+            add: vec![TEST_VOTER.to_string(), TEST_VOTER_2.to_string()],
+            remove: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_allowlist).unwrap();
+        vote(&mut deps, TEST_VOTER, 2, true, 0).unwrap();
+        vote(&mut deps, TEST_VOTER_2, 2, false, 0).unwrap();
+        assert_eq!(tally(&deps, 2), (1, 1));
+        let err = vote(&mut deps, TEST_VOTER, 2, true, 0).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyVoted {}));
+        let err = vote(&mut deps, TEST_VOTER_3, 2, true, 0).unwrap_err();
+        assert!(matches!(err, ContractError::VoterNotEligible {}));
+        let err = vote(&mut deps, TEST_VOTER, 3, true, 1000).unwrap_err();
+        assert!(matches!(err, ContractError::FundsNotAccepted {}));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER),
+            },
+        )
+        .unwrap();
+        let value: TokenStakeResponse = from_json(&res).unwrap();
+        assert_eq!(value.token_balance, Uint128::from(3000u128));
+
+        // delegators need their own allowlist entry to be carried
+        let msg_allowlist = ExecuteMsg::UpdateAllowlist {
+            add: vec!["voter4".to_string()],
+            remove: vec![],
+        };
+        let owner_info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), owner_info, msg_allowlist).unwrap();
+        let msg_delegate = ExecuteMsg::Delegate {
+            to: TEST_VOTER.to_string(),
+        };
+        for delegator in [TEST_VOTER_3, "voter4"] {
+            let info = mock_info(delegator, &[]);
+            execute(deps.as_mut(), mock_env(), info, msg_delegate.clone()).unwrap();
+        }
+        vote(&mut deps, TEST_VOTER, 4, true, 0).unwrap();
+        assert_eq!(tally(&deps, 4), (2, 0));
     }
 
     #[test]
-    fn vote() {
-        const BASE: u128 = 1000;
+    fn conviction_voting() {
         let mut deps = mock_dependencies();
 
         let msg = InstantiateMsg {
             denom: String::from(VOTING_TOKEN),
         };
-        let info = mock_info("creator", &coins(2 * BASE, &msg.denom));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
-
-        let info = mock_info(TEST_VOTER, &coins(1 * BASE, &msg.denom));
-        let yes_vote = true;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let msg_config = ExecuteMsg::UpdateConfig {
+            ballot_mode: None,
+            voter_eligibility: None,
+            voting_mode: Some(VotingMode::Conviction {
+                decay: Decimal::percent(90),
+                threshold: Decimal::percent(50),
+            }),
+            ranking: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        execute(deps.as_mut(), mock_env(), owner_info, msg_config).unwrap();
+        let info = mock_info("funder", &coins(10000, VOTING_TOKEN));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::FundConvictionPool {},
+        )
+        .unwrap();
+        let msg_propose = ExecuteMsg::ProposeFunding {
+            proposal_id: Uint128::from(1u128),
+            beneficiary: "project1".to_string(),
+            requested: Uint128::from(1000u128),
+        };
+        let info = mock_info("project1", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg_propose).unwrap();
 
+        let msg_vote = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let mut funds = coins(100, VOTING_TOKEN);
+        funds.push(Coin::new(5, "other"));
+        let info = mock_info(TEST_VOTER, &funds);
+        let err = execute(deps.as_mut(), mock_env(), info, msg_vote.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::WrongFundCoin { .. }));
+        let info = mock_info(TEST_VOTER, &coins(100, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_vote).unwrap();
+
+        let at_block = |blocks: u64| {
+            let mut env = mock_env();
+            env.block.height += blocks;
+            env
+        };
+        // 100 staked converges to 1000 conviction, 1000 of a 10000 pool needs 555
         let res = query(
             deps.as_ref(),
-            mock_env(),
-            QueryMsg::GetProposal {
+            at_block(7),
+            QueryMsg::Conviction {
                 proposal_id: Uint128::from(1u128),
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(1u128), value.yes_votes);
-        assert_eq!(Uint128::from(0u128), value.no_votes);
+        let value: ConvictionResponse = from_json(&res).unwrap();
+        assert_eq!(value.conviction, Uint128::from(521u128));
+        assert_eq!(value.required, Some(Uint128::from(555u128)));
+
+        let msg_update = ExecuteMsg::UpdateConviction {
+            proposal_id: Uint128::from(1u128),
+        };
+        let info = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), at_block(7), info.clone(), msg_update.clone()).unwrap();
+        assert!(res.messages.is_empty());
+        let res = execute(deps.as_mut(), at_block(8), info.clone(), msg_update.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project1".to_string(),
+                amount: coins(1000, VOTING_TOKEN),
+            })
+        );
+        let err = execute(deps.as_mut(), at_block(9), info, msg_update).unwrap_err();
+        assert!(matches!(err, ContractError::ProposalNotInProgress {}));
+
+        // stakes on another proposal are kept apart and add up
+        let msg_propose = ExecuteMsg::ProposeFunding {
+            proposal_id: Uint128::from(2u128),
+            beneficiary: "project2".to_string(),
+            requested: Uint128::from(1000u128),
+        };
+        let info = mock_info("project2", &[]);
+        execute(deps.as_mut(), at_block(9), info, msg_propose).unwrap();
+        let msg_vote = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(2u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(50, VOTING_TOKEN));
+        execute(deps.as_mut(), at_block(9), info.clone(), msg_vote.clone()).unwrap();
+        execute(deps.as_mut(), at_block(9), info, msg_vote).unwrap();
+
+        let msg_withdraw = ExecuteMsg::WithdrawStake {
+            proposal_id: Uint128::from(1u128),
+        };
+        let info = mock_info(TEST_VOTER, &[]);
+        let res = execute(
+            deps.as_mut(),
+            at_block(9),
+            info.clone(),
+            msg_withdraw.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER.to_string(),
+                amount: coins(100, VOTING_TOKEN),
+            })
+        );
+        let err = execute(deps.as_mut(), at_block(9), info.clone(), msg_withdraw).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+        let msg_withdraw = ExecuteMsg::WithdrawStake {
+            proposal_id: Uint128::from(2u128),
+        };
+        let res = execute(deps.as_mut(), at_block(10), info, msg_withdraw).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER.to_string(),
+                amount: coins(100, VOTING_TOKEN),
+            })
+        );
+    }
+
+    #[test]
+    fn ranked_ballots() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+        create_round(deps.as_mut(), "creator", 1000);
+
+        let ballot = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                      voter: &str,
+                      ranking: &[u128]| {
+            let msg = ExecuteMsg::RankedBallot {
+                round_id: Uint128::from(1u128),
+                ranking: ranking.iter().map(|id| Uint128::from(*id)).collect(),
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(voter, &[]), msg)
+        };
+        let err = ballot(&mut deps, TEST_VOTER, &[2, 1]).unwrap_err();
+        assert!(matches!(err, ContractError::RankingDisabled {}));
+
+        let set_ranking = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                           method: RankingMethod,
+                           winners: u32| {
+            let msg_config = ExecuteMsg::UpdateConfig {
+                ballot_mode: None,
+                voter_eligibility: None,
+                voting_mode: None,
+                ranking: Some(Ranking { method, winners }),
+            };
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                msg_config,
+            )
+            .unwrap();
+        };
+        set_ranking(&mut deps, RankingMethod::Borda, 1);
+
+        let err = ballot(&mut deps, TEST_VOTER, &[2, 3]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBallot {}));
+        let err = ballot(&mut deps, TEST_VOTER, &[2, 2]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBallot {}));
+        let msg_approval = ExecuteMsg::ApprovalBallot {
+            round_id: Uint128::from(1u128),
+            approved: vec![Uint128::from(1u128)],
+        };
+        let info = mock_info(TEST_VOTER, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg_approval).unwrap_err();
+        assert!(matches!(err, ContractError::WrongBallotType {}));
+
+        ballot(&mut deps, TEST_VOTER, &[1, 2]).unwrap();
+        // resubmitting replaces the ballot
+        ballot(&mut deps, TEST_VOTER, &[2, 1]).unwrap();
+        ballot(&mut deps, TEST_VOTER_2, &[2]).unwrap();
+        ballot(&mut deps, TEST_VOTER_3, &[1, 2]).unwrap();
+
+        let results = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::RoundResults {
+                    round_id: Uint128::from(1u128),
+                },
+            )
+            .unwrap();
+            from_json::<RoundResultsResponse>(&res).unwrap()
+        };
+        let value = results(&deps);
+        assert_eq!(value.ballots, 3);
+        assert_eq!(value.winners, vec![Uint128::from(2u128)]);
+        assert_eq!(
+            value.scores,
+            vec![(Uint128::from(1u128), 1), (Uint128::from(2u128), 2)]
+        );
+
+        // ranked ballots don't count once the round switches to approval
+        set_ranking(&mut deps, RankingMethod::Approval, 2);
+        let value = results(&deps);
+        assert_eq!(value.ballots, 0);
+        assert_eq!(
+            value.winners,
+            vec![Uint128::from(1u128), Uint128::from(2u128)]
+        );
+    }
+
+    #[test]
+    fn retro_round() {
+        let mut deps = mock_dependencies();
 
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![
+                (Uint128::from(1u128), "project1".to_string()),
+                (Uint128::from(2u128), "project2".to_string()),
+            ],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: None,
+            retro: Some(RetroFunding {
+                badgeholders: ["badge1", "badge2", "badge3"]
+                    .iter()
+                    .map(|b| Addr::unchecked(*b))
+                    .collect(),
+                aggregation: Aggregation::Median,
+            }),
+        };
+        let info = mock_info("creator", &coins(1000, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
+
+        let allocate = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                        badgeholder: &str,
+                        allocations: &[(u128, u128)]| {
+            let msg = ExecuteMsg::AllocationBallot {
+                round_id: Uint128::from(1u128),
+                allocations: allocations
+                    .iter()
+                    .map(|(id, amount)| (Uint128::from(*id), Uint128::from(*amount)))
+                    .collect(),
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(badgeholder, &[]), msg)
+        };
+        let err = allocate(&mut deps, TEST_VOTER, &[(1, 100)]).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = allocate(&mut deps, "badge1", &[(1, 600), (2, 600)]).unwrap_err();
+        assert!(matches!(err, ContractError::AllocationExceedsBudget {}));
+        let err = allocate(&mut deps, "badge1", &[(3, 100)]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBallot {}));
+
+        allocate(&mut deps, "badge1", &[(1, 600), (2, 400)]).unwrap();
+        allocate(&mut deps, "badge2", &[(1, 200), (2, 300)]).unwrap();
+        allocate(&mut deps, "badge3", &[(1, 500)]).unwrap();
+
+        // medians of [600, 200, 500] and [400, 300, 0]
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::TokenStake {
-                address: Addr::unchecked(TEST_VOTER.to_string()),
+            QueryMsg::EstimatedMatches {
+                round_id: Uint128::from(1u128),
             },
         )
         .unwrap();
-        let token_stake: TokenStakeResponse = from_binary(&res).unwrap();
+        let value: EstimatedMatchesResponse = from_json(&res).unwrap();
+        assert_eq!(value.grants[0].grant, 500);
+        assert_eq!(value.grants[1].grant, 300);
+        assert_eq!(value.leftover, Uint128::from(200u128));
+
+        // completed projects are passed already, the round can be distributed
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_funding).unwrap();
+        let err = allocate(&mut deps, "badge3", &[(2, 500)]).unwrap_err();
+        assert!(matches!(err, ContractError::RoundAlreadyDistributed {}));
 
-        assert_eq!(Uint128::from(1 * BASE), token_stake.token_balance);
+        let msg_claim = ExecuteMsg::ClaimGrant {
+            round_id: Uint128::from(1u128),
+        };
+        let info = mock_info("project1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg_claim).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project1".to_string(),
+                amount: coins(500, VOTING_TOKEN),
+            })
+        );
+    }
 
-        let info = mock_info(TEST_VOTER_2, &coins(1 * BASE, &msg.denom));
+    #[test]
+    fn negative_votes() {
+        let mut deps = mock_dependencies();
 
-        let yes_vote = true;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
         };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let msg_config = ExecuteMsg::UpdateConfig {
+            ballot_mode: None,
+            voter_eligibility: None,
+            voting_mode: Some(VotingMode::Linear),
+            ranking: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_config).unwrap();
+
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![
+                (Uint128::from(1u128), "project1".to_string()),
+                (Uint128::from(2u128), "project2".to_string()),
+            ],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: true,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: None,
+            retro: None,
+        };
+        let info = mock_info("creator", &coins(26900, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
+
+        let vote = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                    voter: &str,
+                    proposal_id: u128,
+                    yes_vote: bool,
+                    amount: u128| {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote,
+            };
+            let info = mock_info(voter, &coins(amount, VOTING_TOKEN));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        };
+        vote(&mut deps, TEST_VOTER, 1, true, 10000);
+        vote(&mut deps, TEST_VOTER_2, 1, true, 2500);
+        vote(&mut deps, "voter3", 1, false, 400);
+        vote(&mut deps, "voter3", 2, true, 10000);
 
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
-
+        // (100 + 50 - 20)^2 against 100^2, the no-vote tokens stay in the pool
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
+            QueryMsg::EstimatedMatches {
+                round_id: Uint128::from(1u128),
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(2u128), value.yes_votes);
-        assert_eq!(Uint128::from(0u128), value.no_votes);
+        let value: EstimatedMatchesResponse = from_json(&res).unwrap();
+        assert_eq!(value.grants[0].grant, 16900);
+        assert_eq!(value.grants[0].collected_vote_funds, 12500);
+        assert_eq!(value.grants[1].grant, 10000);
+        assert_eq!(value.leftover, Uint128::from(400u128));
+
+        // the no-vote tokens are paid out with the rest of the leftover
+        finish_review(&mut deps, 1, true);
+        finish_review(&mut deps, 2, true);
+        let owner_info = mock_info("creator", &[]);
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_funding).unwrap();
+        let msg_withdraw = ExecuteMsg::WithdrawLeftover {
+            round_id: Uint128::from(1u128),
+            recipient: "community".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner_info, msg_withdraw).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "community".to_string(),
+                amount: coins(400, VOTING_TOKEN),
+            })
+        );
+    }
 
-        let res = query(
-            deps.as_ref(),
+    #[test]
+    fn pause() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let guardian_info = mock_info("guardian", &[]);
+        let err = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::TokenStake {
-                address: Addr::unchecked(TEST_VOTER_2.to_string()),
-            },
+            guardian_info.clone(),
+            ExecuteMsg::Pause {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let msg_guardian = ExecuteMsg::UpdateGuardian {
+            guardian: Some("guardian".to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_guardian).unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            guardian_info.clone(),
+            ExecuteMsg::Pause {},
         )
         .unwrap();
-        let token_stake: TokenStakeResponse = from_binary(&res).unwrap();
 
-        assert_eq!(Uint128::from(1 * BASE), token_stake.token_balance);
-        
-        //Third time
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let value: State = from_json(&res).unwrap();
+        assert!(value.paused);
 
-        let yes_vote = false;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+        let vote = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(1u128),
+                yes_vote: true,
+            };
+            let info = mock_info(TEST_VOTER, &coins(MIN_STAKE_AMOUNT, VOTING_TOKEN));
+            execute(deps.as_mut(), mock_env(), info, msg_execute)
+        };
+        let err = vote(&mut deps).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        let msg_claim = ExecuteMsg::ClaimGrant {
+            round_id: Uint128::from(1u128),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("project1", &[]),
+            msg_claim,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        let msg_claw_back = ExecuteMsg::ClawBack {
+            proposal_id: Uint128::from(1u128),
         };
+        let err =
+            execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_claw_back).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        // withdrawals still go through to their own checks
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(TEST_VOTER, &[]),
+            ExecuteMsg::Undelegate {},
+        )
+        .unwrap_err();
+        assert!(!matches!(err, ContractError::Paused {}));
 
-        let info = mock_info(TEST_VOTER_2, &coins(4 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        // the owner can lift a guardian's pause
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            owner_info,
+            ExecuteMsg::Unpause {},
+        )
+        .unwrap();
+        vote(&mut deps).unwrap();
+    }
+
+    #[test]
+    fn cw3_queries() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info, msg).unwrap();
+
+        let msg_propose = ExecuteMsg::Propose {
+            proposal_id: Uint128::from(1u128),
+            fund_address: "grantee".to_string(),
+            msgs: vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: "grantee".to_string(),
+                amount: coins(500, VOTING_TOKEN),
+            })],
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("grantee", &[]),
+            msg_propose,
+        )
+        .unwrap();
+        let vote = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                    voter: &str,
+                    proposal_id: u128,
+                    yes_vote: bool| {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote,
+            };
+            let info = mock_info(voter, &coins(MIN_STAKE_AMOUNT, VOTING_TOKEN));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        };
+        vote(&mut deps, TEST_VOTER_2, 1, false);
+        vote(&mut deps, TEST_VOTER, 1, true);
+        vote(&mut deps, TEST_VOTER, 2, true);
+        // has no cw3 id and is left out of the list
+        vote(&mut deps, TEST_VOTER, u64::MAX as u128 + 1, true);
 
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
-            },
+            QueryMsg::Proposal { proposal_id: 1 },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(2u128), value.yes_votes);
-        assert_eq!(Uint128::from(1u128), value.no_votes);
+        let value: ProposalResponse = from_json(&res).unwrap();
+        assert_eq!(value.id, 1);
+        assert_eq!(value.description, "grantee");
+        assert_eq!(value.msgs.len(), 1);
+        assert_eq!(value.status, Cw3Status::Open);
+        assert_eq!(value.threshold, majority_threshold());
 
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::TokenStake {
-                address: Addr::unchecked(TEST_VOTER_2.to_string()),
+            QueryMsg::ListProposals {
+                start_after: Some(1),
+                limit: None,
             },
         )
         .unwrap();
-        let token_stake: TokenStakeResponse = from_binary(&res).unwrap();
-
-        assert_eq!(Uint128::from(5 * BASE), token_stake.token_balance);
-
-        //Four times
-        let yes_vote = false;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
-        };
-
-        let info = mock_info(TEST_VOTER_2, &coins(9 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        let value: ProposalListResponse = from_json(&res).unwrap();
+        assert_eq!(value.proposals.len(), 1);
+        assert_eq!(value.proposals[0].id, 2);
 
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
+            QueryMsg::ListVotes {
+                proposal_id: 1,
+                start_after: None,
+                limit: None,
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(2u128), value.yes_votes);
-        assert_eq!(Uint128::from(2u128), value.no_votes);
+        let value: VoteListResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.votes,
+            vec![
+                VoteInfo {
+                    proposal_id: 1,
+                    voter: TEST_VOTER.to_string(),
+                    vote: Cw3Vote::Yes,
+                    weight: 1,
+                },
+                VoteInfo {
+                    proposal_id: 1,
+                    voter: TEST_VOTER_2.to_string(),
+                    vote: Cw3Vote::No,
+                    weight: 1,
+                },
+            ]
+        );
 
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::TokenStake {
-                address: Addr::unchecked(TEST_VOTER_2.to_string()),
+            QueryMsg::Vote {
+                proposal_id: 2,
+                voter: TEST_VOTER_2.to_string(),
             },
         )
         .unwrap();
-        let token_stake: TokenStakeResponse = from_binary(&res).unwrap();
+        let value: VoteResponse = from_json(&res).unwrap();
+        assert_eq!(value.vote, None);
+    }
 
-        assert_eq!(Uint128::from(14 * BASE), token_stake.token_balance);
+    #[test]
+    fn events() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
 
-        // Vote another proposal id
-        let yes_vote = false;
-        let proposal_id = Uint128::from(2u128);
         let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(MIN_STAKE_AMOUNT, VOTING_TOKEN));
+        let res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("vote")
+                .add_attribute("voter", TEST_VOTER)
+                .add_attribute("proposal_id", "1")
+                .add_attribute("yes_vote", "true")
+                .add_attribute("votes", "1")
+                .add_attribute("amount", MIN_STAKE_AMOUNT.to_string())
+                .add_attribute("fee", "0")
+                .add_attribute("refund", "0")
+                .add_attribute("yes_votes", "1")
+                .add_attribute("no_votes", "0")]
+        );
+
+        let msg_end_vote = ExecuteMsg::EndVote {
+            proposal_id: Uint128::from(1u128),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_end_vote).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("end_vote")
+                .add_attribute("proposal_id", "1")
+                .add_attribute("from", "in_progress")
+                .add_attribute("to", "reviewed")
+                .add_attribute("yes_votes", "1")
+                .add_attribute("no_votes", "0")]
+        );
+
+        // funding reports every grant of the round
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![
+                (Uint128::from(2u128), "project2".to_string()),
+                (Uint128::from(3u128), "project3".to_string()),
+            ],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: None,
+            retro: Some(RetroFunding {
+                badgeholders: vec![Addr::unchecked("badge1")],
+                aggregation: Aggregation::Median,
+            }),
         };
+        let info = mock_info("creator", &coins(1000, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
+        let msg_allocate = ExecuteMsg::AllocationBallot {
+            round_id: Uint128::from(1u128),
+            allocations: vec![(Uint128::from(2u128), Uint128::from(700u128))],
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("badge1", &[]),
+            msg_allocate,
+        )
+        .unwrap();
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner_info, msg_funding).unwrap();
+        assert_eq!(res.events.len(), 3);
+        assert_eq!(res.events[0].ty, "funding_proposal");
+        assert_eq!(
+            res.events[1],
+            Event::new("grant")
+                .add_attribute("round_id", "1")
+                .add_attribute("proposal_id", "2")
+                .add_attribute("recipient", "project2")
+                .add_attribute("grant", "700")
+                .add_attribute("fee", "0")
+                .add_attribute("collected_vote_funds", "0")
+                .add_attribute("eligible", "true")
+        );
+        assert_eq!(res.events[2].attributes[3].value, "0");
+    }
 
-        let info = mock_info(TEST_VOTER_3, &coins(1 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+    #[test]
+    fn overpayment_refund() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info, msg).unwrap();
 
+        let vote = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, funds: &[Coin]| {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(1u128),
+                yes_vote: true,
+            };
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(TEST_VOTER, funds),
+                msg_execute,
+            )
+        };
+        let err = vote(&mut deps, &coins(MIN_STAKE_AMOUNT - 1, VOTING_TOKEN)).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFundsSent {}));
+        let mut funds = coins(MIN_STAKE_AMOUNT, VOTING_TOKEN);
+        funds.push(coin(10, "other"));
+        let err = vote(&mut deps, &funds).unwrap_err();
+        assert!(matches!(err, ContractError::WrongFundCoin { .. }));
+
+        // the excess over the quadratic cost is sent back
+        let res = vote(&mut deps, &coins(MIN_STAKE_AMOUNT + 500, VOTING_TOKEN)).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER.to_string(),
+                amount: coins(500, VOTING_TOKEN),
+            })
+        );
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(2u128),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER),
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(0u128), value.yes_votes);
-        assert_eq!(Uint128::from(1u128), value.no_votes);
+        let value: TokenStakeResponse = from_json(&res).unwrap();
+        assert_eq!(value.token_balance, Uint128::from(MIN_STAKE_AMOUNT));
+
+        // exact payments need no refund
+        let res = vote(&mut deps, &coins(4 * MIN_STAKE_AMOUNT, VOTING_TOKEN)).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn platform_fee() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let update_fee =
+            |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, sender: &str, bps: u16| {
+                let msg_fee = ExecuteMsg::UpdateFee {
+                    fee: Some(PlatformFee {
+                        bps,
+                        treasury: Addr::unchecked("treasury"),
+                    }),
+                };
+                execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg_fee)
+            };
+        let err = update_fee(&mut deps, TEST_VOTER, 500).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = update_fee(&mut deps, "creator", MAX_FEE_BPS + 1).unwrap_err();
+        assert!(matches!(err, ContractError::FeeTooHigh {}));
+        update_fee(&mut deps, "creator", 500).unwrap();
+
+        let treasury = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::Treasury {}).unwrap();
+            let value: TreasuryResponse = from_json(&res).unwrap();
+            value.balance.u128()
+        };
 
+        // 5% of the vote payment goes to the treasury, the rest is staked
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(MIN_STAKE_AMOUNT, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
         let res = query(
             deps.as_ref(),
             mock_env(),
             QueryMsg::TokenStake {
-                address: Addr::unchecked(TEST_VOTER_3.to_string()),
+                address: Addr::unchecked(TEST_VOTER),
             },
         )
         .unwrap();
-        let token_stake: TokenStakeResponse = from_binary(&res).unwrap();
-
-        assert_eq!(Uint128::from(1 * BASE), token_stake.token_balance);
-
-        //Voter 3 vote for proposal 2
-        let yes_vote = true;
-        let proposal_id = Uint128::from(2u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
-        };
-
-        let info = mock_info(TEST_VOTER_3, &coins(4 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
-
+        let value: TokenStakeResponse = from_json(&res).unwrap();
+        assert_eq!(value.token_balance, Uint128::from(950u128));
+        assert_eq!(treasury(&deps), 50);
+
+        // and 5% of the matched grant
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![(Uint128::from(2u128), "project2".to_string())],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: None,
+            retro: Some(RetroFunding {
+                badgeholders: vec![Addr::unchecked("badge1")],
+                aggregation: Aggregation::Median,
+            }),
+        };
+        let info = mock_info("creator", &coins(1000, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
+        let msg_allocate = ExecuteMsg::AllocationBallot {
+            round_id: Uint128::from(1u128),
+            allocations: vec![(Uint128::from(2u128), Uint128::from(700u128))],
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("badge1", &[]),
+            msg_allocate,
+        )
+        .unwrap();
+        // estimates and the distribution report the match net of the fee
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(2u128),
+            QueryMsg::EstimatedMatches {
+                round_id: Uint128::from(1u128),
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(1u128), value.yes_votes);
-        assert_eq!(Uint128::from(1u128), value.no_votes);
-
+        let value: EstimatedMatchesResponse = from_json(&res).unwrap();
+        assert_eq!(value.grants[0].grant, 665);
+        assert_eq!(value.fees, vec![Uint128::from(35u128)]);
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_funding).unwrap();
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::TokenStake {
-                address: Addr::unchecked(TEST_VOTER_3.to_string()),
+            QueryMsg::Distribution {
+                round_id: Uint128::from(1u128),
             },
         )
         .unwrap();
-        let token_stake: TokenStakeResponse = from_binary(&res).unwrap();
-
-        assert_eq!(Uint128::from(5 * BASE), token_stake.token_balance);
+        let value: Distribution = from_json(&res).unwrap();
+        assert_eq!(value.grants[0].grant.grant, 665);
+        assert_eq!(value.grants[0].fee, Uint128::from(35u128));
+        let msg_claim = ExecuteMsg::ClaimGrant {
+            round_id: Uint128::from(1u128),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("project2", &[]),
+            msg_claim,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project2".to_string(),
+                amount: coins(665, VOTING_TOKEN),
+            })
+        );
+        assert_eq!(treasury(&deps), 85);
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::WithdrawFees {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::WithdrawFees {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(85, VOTING_TOKEN),
+            })
+        );
+        assert_eq!(treasury(&deps), 0);
+
+        // clearing the fee pays out what was collected since
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(4 * MIN_STAKE_AMOUNT, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        assert_eq!(treasury(&deps), 200);
+        let msg_fee = ExecuteMsg::UpdateFee { fee: None };
+        let res = execute(deps.as_mut(), mock_env(), owner_info, msg_fee).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(200, VOTING_TOKEN),
+            })
+        );
+        assert_eq!(treasury(&deps), 0);
     }
 
     #[test]
-    fn end_vote_passed() {
+    fn fees_on_reveals_and_stakes() {
         let mut deps = mock_dependencies();
 
-        const BASE: u128 = 1000;
         let msg = InstantiateMsg {
             denom: String::from(VOTING_TOKEN),
         };
-        let owner_info = mock_info("creator", &coins(1 * BASE, &msg.denom));
-        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
-
-        let voter_info = mock_info("anyone", &coins(1 * BASE, &msg.denom));
-
-        let yes_vote = true;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+        let msg_fee = ExecuteMsg::UpdateFee {
+            fee: Some(PlatformFee {
+                bps: 500,
+                treasury: Addr::unchecked("treasury"),
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_fee).unwrap();
+        let treasury = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::Treasury {}).unwrap();
+            let value: TreasuryResponse = from_json(&res).unwrap();
+            value.balance.u128()
+        };
+        let token_balance = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::TokenStake {
+                    address: Addr::unchecked(TEST_VOTER_2),
+                },
+            )
+            .unwrap();
+            let value: TokenStakeResponse = from_json(&res).unwrap();
+            value.token_balance.u128()
         };
-        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
-
-        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
-
-        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_vote).unwrap();
 
-        let res = query(
-            deps.as_ref(),
+        let msg_vote = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(MIN_STAKE_AMOUNT, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_vote).unwrap();
+        assert_eq!(treasury(&deps), 50);
+
+        // a revealed vote pays the fee on its cost, not on the deposit
+        let msg_config = ExecuteMsg::UpdateConfig {
+            ballot_mode: Some(BallotMode::CommitReveal {
+                reveal_period: 100,
+                unrevealed: UnrevealedPolicy::Forfeit,
+            }),
+            voter_eligibility: None,
+            voting_mode: None,
+            ranking: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_config).unwrap();
+        let preimage = format!("{}:{}:{}:{}:{}", TEST_VOTER_2, 1, 1, true, "salt");
+        let msg_commit = ExecuteMsg::CommitVote {
+            proposal_id: Uint128::from(1u128),
+            commitment: Binary::from(Sha256::digest(preimage.as_bytes()).to_vec()),
+        };
+        let info = mock_info(TEST_VOTER_2, &coins(3 * MIN_STAKE_AMOUNT, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_commit).unwrap();
+        let msg_end = ExecuteMsg::EndVote {
+            proposal_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_end).unwrap();
+        let msg_reveal = ExecuteMsg::RevealVote {
+            proposal_id: Uint128::from(1u128),
+            votes: 1,
+            yes: true,
+            salt: "salt".to_string(),
+        };
+        let info = mock_info(TEST_VOTER_2, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg_reveal).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER_2.to_string(),
+                amount: coins(2 * MIN_STAKE_AMOUNT, VOTING_TOKEN),
+            })
+        );
+        assert_eq!(treasury(&deps), 100);
+        assert_eq!(token_balance(&deps), 950);
+
+        // so does a conviction stake, and only the rest can be withdrawn
+        let msg_config = ExecuteMsg::UpdateConfig {
+            ballot_mode: Some(BallotMode::Open),
+            voter_eligibility: None,
+            voting_mode: Some(VotingMode::Conviction {
+                decay: Decimal::percent(90),
+                threshold: Decimal::percent(50),
+            }),
+            ranking: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_config).unwrap();
+        let msg_propose = ExecuteMsg::ProposeFunding {
+            proposal_id: Uint128::from(2u128),
+            beneficiary: "project2".to_string(),
+            requested: Uint128::from(1000u128),
+        };
+        execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
-            },
+            mock_info("project2", &[]),
+            msg_propose,
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-
-        assert_eq!(value.status, ProposalStatus::Reviewed);
+        let msg_vote = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(2u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER_2, &coins(200, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_vote).unwrap();
+        assert_eq!(treasury(&deps), 110);
+        let msg_withdraw = ExecuteMsg::WithdrawStake {
+            proposal_id: Uint128::from(2u128),
+        };
+        let info = mock_info(TEST_VOTER_2, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg_withdraw).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER_2.to_string(),
+                amount: coins(190, VOTING_TOKEN),
+            })
+        );
+        assert_eq!(token_balance(&deps), 950);
     }
 
     #[test]
-    fn end_vote_rejected() {
+    fn simulate_contribution_by_address() {
         let mut deps = mock_dependencies();
 
         const BASE: u128 = 1000;
         let msg = InstantiateMsg {
             denom: String::from(VOTING_TOKEN),
         };
-        let owner_info = mock_info("creator", &coins(1 * BASE, &msg.denom));
+        let owner_info = mock_info("creator", &[]);
         let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
-
-        let voter_info = mock_info("anyone", &coins(1 * BASE, &msg.denom));
-
-        let yes_vote = false;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+        create_round(deps.as_mut(), "creator", 10 * BASE);
+        let msg_execute = ExecuteMsg::SetTrustWeights {
+            weights: vec![(TEST_VOTER_2.to_string(), Decimal::percent(50))],
+            default: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
-
-        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
-
-        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_vote).unwrap();
+        execute(deps.as_mut(), mock_env(), owner_info, msg_execute).unwrap();
+
+        let votes = [
+            (TEST_VOTER, 1u128, BASE),
+            (TEST_VOTER_2, 1u128, BASE),
+            (TEST_VOTER_3, 2u128, BASE),
+            (TEST_VOTER_3, 2u128, 4 * BASE),
+        ];
+        for (voter, proposal_id, amount) in votes {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            };
+            let info = mock_info(voter, &coins(amount, &msg.denom));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        }
 
+        // voter2 tops up their own half-weight term, not voter1's equal one:
+        // (31 + 44 * 0.5)^2 = 2809 against 4900
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
+            QueryMsg::SimulateContribution {
                 proposal_id: Uint128::from(1u128),
+                amount: Uint128::from(BASE),
+                contributor: Some(Addr::unchecked(TEST_VOTER_2)),
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-
-        assert_eq!(value.status, ProposalStatus::Rejected);
+        let value: SimulateContributionResponse = from_json(&res).unwrap();
+        assert_eq!(value.current_match, Uint128::from(3015u128));
+        assert_eq!(value.simulated_match, Uint128::from(3643u128));
+        assert_eq!(value.match_change, Int128::new(628));
     }
 
-
-
     #[test]
-    fn review() {
+    fn leftover_and_refunds() {
         let mut deps = mock_dependencies();
 
         const BASE: u128 = 1000;
         let msg = InstantiateMsg {
             denom: String::from(VOTING_TOKEN),
         };
-        let owner_info = mock_info("creator", &coins(1 * BASE, &msg.denom));
+        let owner_info = mock_info("creator", &[]);
         let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![
+                (Uint128::from(1u128), "project1".to_string()),
+                (Uint128::from(2u128), "project2".to_string()),
+            ],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: Some(MatchingCap::Absolute {
+                    amount: Uint128::from(4 * BASE),
+                }),
+                eligibility: None,
+            },
+            milestones_required: false,
+            vesting: None,
+            retro: None,
+        };
+        let info = mock_info("creator", &coins(10 * BASE, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
+
+        let votes = [(TEST_VOTER, 1u128, BASE), (TEST_VOTER_2, 2u128, BASE)];
+        for (voter, proposal_id, amount) in votes {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            };
+            let info = mock_info(voter, &coins(amount, &msg.denom));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        }
 
-        let voter_info = mock_info("anyone", &coins(1 * BASE, &msg.denom));
+        let msg_refund = ExecuteMsg::RefundContribution {
+            proposal_id: Uint128::from(2u128),
+        };
+        let info = mock_info(TEST_VOTER_2, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg_refund.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::ProposalNotRejected {}));
+
+        finish_review(&mut deps, 1, true);
+        finish_review(&mut deps, 2, false);
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_funding).unwrap();
 
-        let yes_vote = true;
-        let proposal_id = Uint128::from(1u128);
+        // the pool is paid out, late votes would be locked in the contract
         let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
         };
-        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
-
-        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
-
-        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_vote).unwrap();
-
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
-            },
-        )
-        .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-
-        assert_eq!(value.status, ProposalStatus::Reviewed);
-
-
-        let approved = true;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Review {
-            proposal_id,
-            approved,
+        let info = mock_info(TEST_VOTER_2, &coins(BASE, VOTING_TOKEN));
+        let err = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap_err();
+        assert!(matches!(err, ContractError::ProposalNotInProgress {}));
+
+        // the cap leaves 6000 of the pool unallocated
+        let msg_withdraw = ExecuteMsg::WithdrawLeftover {
+            round_id: Uint128::from(1u128),
+            recipient: "community".to_string(),
         };
-
-        let info = mock_info("reviewer1", &coins(1 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
-
-        let res = query(
-            deps.as_ref(),
+        let info = mock_info(TEST_VOTER, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg_withdraw.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
-            },
+            owner_info.clone(),
+            msg_withdraw.clone(),
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(1u128), value.yes_votes);
-        assert_eq!(Uint128::from(0u128), value.no_votes);
-        
-
-        let approved = true;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Review {
-            proposal_id,
-            approved,
-        };
-
-        let info = mock_info("reviewer2", &coins(1 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "community".to_string(),
+                amount: coins(6 * BASE, VOTING_TOKEN),
+            })
+        );
+        let err = execute(deps.as_mut(), mock_env(), owner_info, msg_withdraw).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+
+        // contributors to the rejected proposal take their stake back
+        let info = mock_info(TEST_VOTER_2, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg_refund.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TEST_VOTER_2.to_string(),
+                amount: coins(BASE, VOTING_TOKEN),
+            })
+        );
+        let err = execute(deps.as_mut(), mock_env(), info, msg_refund).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToRefund {}));
 
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
+            QueryMsg::TokenStake {
+                address: Addr::unchecked(TEST_VOTER_2),
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::from(2u128), value.yes_votes);
-        assert_eq!(Uint128::from(0u128), value.no_votes);
-
+        let token_stake: TokenStakeResponse = from_json(&res).unwrap();
+        assert_eq!(token_stake.token_balance, Uint128::zero());
     }
 
-
     #[test]
-    fn end_review_passed() {
+    fn milestone_threshold() {
         let mut deps = mock_dependencies();
 
         const BASE: u128 = 1000;
         let msg = InstantiateMsg {
             denom: String::from(VOTING_TOKEN),
         };
-        let owner_info = mock_info("creator", &coins(1 * BASE, &msg.denom));
+        let owner_info = mock_info("creator", &[]);
         let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
 
-        let voter_info = mock_info("anyone", &coins(1 * BASE, &msg.denom));
-
-        let yes_vote = true;
-        let proposal_id = Uint128::from(1u128);
+        let msg_reviewers = ExecuteMsg::UpdateReviewers {
+            add: vec![
+                "committee1".to_string(),
+                "committee2".to_string(),
+                "committee3".to_string(),
+            ],
+            remove: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_reviewers).unwrap();
+        let msg_threshold = ExecuteMsg::UpdateMilestoneThreshold {
+            threshold: Decimal::one(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg_threshold.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err =
+            execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_threshold).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidThreshold {}));
+
+        let msg_round = ExecuteMsg::CreateRound {
+            round_id: Uint128::from(1u128),
+            proposals: vec![(Uint128::from(1u128), "project1".to_string())],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: true,
+            vesting: None,
+            retro: None,
+        };
+        let info = mock_info("creator", &coins(10 * BASE, &msg.denom));
+        execute(deps.as_mut(), mock_env(), info, msg_round).unwrap();
         let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
         };
-        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
-
-        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
-
-        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_end_vote).unwrap();
+        let info = mock_info(TEST_VOTER, &coins(BASE, &msg.denom));
+        execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        finish_review(&mut deps, 1, true);
+        let msg_funding = ExecuteMsg::FundingProposal {
+            round_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_funding).unwrap();
 
-        let res = query(
-            deps.as_ref(),
+        let project_info = mock_info("project1", &[]);
+        let msg_milestones = ExecuteMsg::SetMilestones {
+            proposal_id: Uint128::from(1u128),
+            milestones: vec![Decimal::percent(50), Decimal::percent(50)],
+        };
+        execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
-            },
+            project_info.clone(),
+            msg_milestones,
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-
-        assert_eq!(value.status, ProposalStatus::Reviewed);
-
-
-        let approved = true;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Review {
-            proposal_id,
-            approved,
+        let msg_claim = ExecuteMsg::ClaimGrant {
+            round_id: Uint128::from(1u128),
         };
+        execute(deps.as_mut(), mock_env(), project_info.clone(), msg_claim).unwrap();
 
-        let info = mock_info("reviewer1", &coins(1 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
-
-
-        let msg_end_review = ExecuteMsg::EndReview { proposal_id };
+        let approve = |reviewer: &str, milestone: u32, approved: bool| {
+            let msg = ExecuteMsg::ApproveMilestone {
+                proposal_id: Uint128::from(1u128),
+                milestone,
+                approved,
+            };
+            (mock_info(reviewer, &[]), msg)
+        };
+        let msg_claim_milestone = ExecuteMsg::ClaimMilestone {
+            proposal_id: Uint128::from(1u128),
+        };
 
-        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_review).unwrap();
+        // one of three reviewers is not enough
+        let (info, msg) = approve("committee1", 0, true);
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MilestoneAlreadyReviewed {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            project_info.clone(),
+            msg_claim_milestone.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
 
-        let res = query(
-            deps.as_ref(),
+        let (info, msg) = approve("committee2", 0, true);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
-            },
+            project_info.clone(),
+            msg_claim_milestone,
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "project1".to_string(),
+                amount: coins(5500, VOTING_TOKEN),
+            })
+        );
+
+        // two rejections leave the approvals out of reach
+        let (info, msg) = approve("committee1", 1, false);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let (info, msg) = approve("committee3", 1, false);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.events[0].attributes[5].value, "rejected");
+
+        // the returned tranche can leave the contract
+        let msg_withdraw = ExecuteMsg::WithdrawLeftover {
+            round_id: Uint128::from(1u128),
+            recipient: "community".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner_info, msg_withdraw).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "community".to_string(),
+                amount: coins(5500, VOTING_TOKEN),
+            })
+        );
+    }
 
-        assert_eq!(value.status, ProposalStatus::Passed);
+    #[test]
+    fn quadratic_cost_overflow() {
+        let mut deps = mock_dependencies();
 
+        let msg = InstantiateMsg {
+            denom: String::from(VOTING_TOKEN),
+        };
+        let owner_info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+        let msg_vote = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER_2, &coins(1000, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_vote).unwrap();
+
+        let msg_config = ExecuteMsg::UpdateConfig {
+            ballot_mode: Some(BallotMode::CommitReveal {
+                reveal_period: 100,
+                unrevealed: UnrevealedPolicy::Forfeit,
+            }),
+            voter_eligibility: None,
+            voting_mode: None,
+            ranking: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_config).unwrap();
 
+        let preimage = format!("{}:{}:{}:{}:{}", TEST_VOTER, 1, u64::MAX, true, "salt");
+        let msg_commit = ExecuteMsg::CommitVote {
+            proposal_id: Uint128::from(1u128),
+            commitment: Binary::from(Sha256::digest(preimage.as_bytes()).to_vec()),
+        };
+        let info = mock_info(TEST_VOTER, &coins(1000, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info.clone(), msg_commit).unwrap();
+        let msg_end = ExecuteMsg::EndVote {
+            proposal_id: Uint128::from(1u128),
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_end).unwrap();
+
+        // the price of u64::MAX votes does not fit, instead of wrapping around
+        let msg_reveal = ExecuteMsg::RevealVote {
+            proposal_id: Uint128::from(1u128),
+            votes: u64::MAX,
+            yes: true,
+            salt: "salt".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg_reveal).unwrap_err();
+        assert!(matches!(err, ContractError::OverflowError {}));
     }
 
     #[test]
-    fn end_review_rejected() {
+    fn default_trust_weight() {
         let mut deps = mock_dependencies();
 
         const BASE: u128 = 1000;
         let msg = InstantiateMsg {
             denom: String::from(VOTING_TOKEN),
         };
-        let owner_info = mock_info("creator", &coins(1 * BASE, &msg.denom));
+        let owner_info = mock_info("creator", &[]);
         let _res = instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg.clone()).unwrap();
+        create_round(deps.as_mut(), "creator", 10 * BASE);
 
-        let voter_info = mock_info("anyone", &coins(1 * BASE, &msg.denom));
-
-        let yes_vote = true;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Vote {
-            proposal_id,
-            yes_vote,
+        let msg_weights = |default: Decimal| ExecuteMsg::SetTrustWeights {
+            weights: vec![],
+            default: Some(default),
         };
-        let _res = execute(deps.as_mut(), mock_env(), voter_info, msg_execute).unwrap();
-
-        let msg_end_vote = ExecuteMsg::EndVote { proposal_id };
-
-        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg_end_vote).unwrap();
-
-        let res = query(
-            deps.as_ref(),
+        let err = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::GetProposal {
-                proposal_id: Uint128::from(1u128),
-            },
+            owner_info.clone(),
+            msg_weights(Decimal::percent(150)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTrustWeight {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            owner_info.clone(),
+            msg_weights(Decimal::percent(25)),
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-
-        assert_eq!(value.status, ProposalStatus::Reviewed);
-
-
-        let approved = false;
-        let proposal_id = Uint128::from(1u128);
-        let msg_execute = ExecuteMsg::Review {
-            proposal_id,
-            approved,
+        // voting stays open, the allowlist only marks verified contributors
+        let msg_allowlist = ExecuteMsg::UpdateAllowlist {
+            add: vec![TEST_VOTER.to_string()],
+            remove: vec![],
         };
+        execute(deps.as_mut(), mock_env(), owner_info, msg_allowlist).unwrap();
+
+        let votes = [
+            (TEST_VOTER, 1u128),
+            (TEST_VOTER_2, 1u128),
+            (TEST_VOTER_3, 2u128),
+        ];
+        for (voter, proposal_id) in votes {
+            let msg_execute = ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            };
+            let info = mock_info(voter, &coins(BASE, &msg.denom));
+            execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+        }
 
-        let info = mock_info("reviewer1", &coins(1 * BASE, &msg.denom));
-        let _res = execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
-
-
-        let msg_end_review = ExecuteMsg::EndReview { proposal_id };
-
-        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg_end_review).unwrap();
-
+        // (31 + 31 * 0.25)^2 = 1444 against (31 * 0.25)^2 = 49
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::GetProposal {
+            QueryMsg::EstimatedMatch {
                 proposal_id: Uint128::from(1u128),
             },
         )
         .unwrap();
-        let value: GetProposalResponse = from_binary(&res).unwrap();
-
-        assert_eq!(value.status, ProposalStatus::Rejected);
-
-
+        let value: EstimatedMatchResponse = from_json(&res).unwrap();
+        assert_eq!(value.grant.grant, 9671);
     }
 
+    #[test]
+    fn migrate_legacy_storage() {
+        let mut deps = mock_dependencies();
 
-
-
+        // the layouts before rounds, delegation and Uint128 proposal ids
+        #[cosmwasm_schema::cw_serde]
+        struct BaselineState {
+            denom: String,
+            owner: Addr,
+        }
+        #[cosmwasm_schema::cw_serde]
+        struct BaselineProposal {
+            fund_address: String,
+            status: ProposalStatus,
+            yes_votes: Uint128,
+            no_votes: Uint128,
+            voters: Vec<Addr>,
+        }
+        #[cosmwasm_schema::cw_serde]
+        struct BaselineVoter {
+            vote_count: Vec<(Uint128, u64)>,
+        }
+        let storage = deps.as_mut().storage;
+        cw_storage_plus::Item::<BaselineState>::new("state")
+            .save(
+                storage,
+                &BaselineState {
+                    denom: VOTING_TOKEN.to_string(),
+                    owner: Addr::unchecked("creator"),
+                },
+            )
+            .unwrap();
+        let proposal_id = Uint128::from(1u128).to_be_bytes();
+        cw_storage_plus::Map::<&[u8], BaselineProposal>::new("proposals")
+            .save(
+                storage,
+                &proposal_id,
+                &BaselineProposal {
+                    fund_address: "project1".to_string(),
+                    status: ProposalStatus::InProgress,
+                    yes_votes: Uint128::from(1u128),
+                    no_votes: Uint128::zero(),
+                    voters: vec![Addr::unchecked(TEST_VOTER)],
+                },
+            )
+            .unwrap();
+        cw_storage_plus::Map::<&[u8], BaselineVoter>::new("voter")
+            .save(
+                storage,
+                TEST_VOTER.as_bytes(),
+                &BaselineVoter {
+                    vote_count: vec![(Uint128::from(1u128), 1)],
+                },
+            )
+            .unwrap();
+        cw_storage_plus::Map::<&[u8], LegacyBalanceVote>::new("balance")
+            .save(
+                storage,
+                TEST_VOTER.as_bytes(),
+                &LegacyBalanceVote {
+                    token_balance: Uint128::from(MIN_STAKE_AMOUNT),
+                    locked_tokens: vec![(1, Uint128::from(MIN_STAKE_AMOUNT))],
+                    participated_polls: vec![1],
+                },
+            )
+            .unwrap();
+        // already in the current layout
+        BALANCES
+            .save(
+                storage,
+                TEST_VOTER_2.as_bytes(),
+                &BalanceVote {
+                    token_balance: Uint128::from(MIN_STAKE_AMOUNT),
+                    locked_tokens: vec![(Uint128::from(2u128), Uint128::from(MIN_STAKE_AMOUNT))],
+                    locked_against: vec![(Uint128::from(2u128), Uint128::from(MIN_STAKE_AMOUNT))],
+                    participated_polls: vec![],
+                },
+            )
+            .unwrap();
+
+        // only this contract at the same or an older version is migrated
+        set_contract_version(deps.as_mut().storage, "crates.io:other", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrate { .. }));
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrateVersion { .. }));
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.0.9").unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(
+            res.events[0],
+            Event::new("migrate")
+                .add_attribute("version", CONTRACT_VERSION)
+                .add_attribute("balances", "2")
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let value: State = from_json(&res).unwrap();
+        assert_eq!(value.owner, Addr::unchecked("creator"));
+        assert_eq!(value.voting_mode, VotingMode::Quadratic);
+        assert_eq!(value.milestone_threshold, Decimal::percent(50));
+        assert_eq!(value.default_trust_weight, Decimal::one());
+        assert_eq!(value.unit_price, Uint128::from(MIN_STAKE_AMOUNT));
+        let proposal = PROPOSALS.load(&deps.storage, &proposal_id).unwrap();
+        assert_eq!(proposal.start_height, 0);
+        assert_eq!(proposal.round_id, None);
+        let voter = VOTERS.load(&deps.storage, TEST_VOTER.as_bytes()).unwrap();
+        assert!(voter.against.is_empty());
+        let balance = BALANCES.load(&deps.storage, TEST_VOTER.as_bytes()).unwrap();
+        assert_eq!(
+            balance.locked_tokens,
+            vec![(Uint128::from(1u128), Uint128::from(MIN_STAKE_AMOUNT))]
+        );
+        assert!(balance.locked_against.is_empty());
+        let balance = BALANCES
+            .load(&deps.storage, TEST_VOTER_2.as_bytes())
+            .unwrap();
+        assert_eq!(balance.locked_against.len(), 1);
+        let version = cw2::get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.version, CONTRACT_VERSION);
+
+        // the migrated voter pays for a second vote on top of the legacy one
+        let msg_execute = ExecuteMsg::Vote {
+            proposal_id: Uint128::from(1u128),
+            yes_vote: true,
+        };
+        let info = mock_info(TEST_VOTER, &coins(4 * MIN_STAKE_AMOUNT, VOTING_TOKEN));
+        execute(deps.as_mut(), mock_env(), info, msg_execute).unwrap();
+    }
 }
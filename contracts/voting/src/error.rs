@@ -12,7 +12,7 @@ pub enum ContractError {
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
 
-    #[error("funds sent should be equal by voting weight")]
+    #[error("funds sent should be at least the required cost")]
     InsufficientFundsSent {},
 
     #[error("Overflow")]
@@ -35,6 +35,169 @@ pub enum ContractError {
 
     #[error("CLR algorithm requires a budget constrain")]
     CLRConstrainRequired {},
-    
 
+    #[error("Round already exists")]
+    RoundAlreadyExists {},
+
+    #[error("Proposal {proposal_id} already belongs to a round")]
+    ProposalAlreadyInRound { proposal_id: String },
+
+    #[error("Round not found")]
+    RoundNotFound {},
+
+    #[error("Proposal {proposal_id} has not finished review")]
+    RoundNotFinalized { proposal_id: String },
+
+    #[error("Round already distributed")]
+    RoundAlreadyDistributed {},
+
+    #[error("Round not distributed yet")]
+    RoundNotDistributed {},
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("Proposal not passed")]
+    ProposalNotPassed {},
+
+    #[error("Milestone percentages must be positive and sum to 100%")]
+    InvalidMilestones {},
+
+    #[error("Proposal {proposal_id} must declare milestones before claiming")]
+    MilestonesRequired { proposal_id: String },
+
+    #[error("Escrow not found")]
+    EscrowNotFound {},
+
+    #[error("Escrow already funded")]
+    EscrowAlreadyFunded {},
+
+    #[error("Escrow not funded yet")]
+    EscrowNotFunded {},
+
+    #[error("Milestone not found")]
+    MilestoneNotFound {},
+
+    #[error("Milestone already decided")]
+    MilestoneNotPending {},
+
+    #[error("Vesting must end after it starts")]
+    InvalidVesting {},
+
+    #[error("Stream not found")]
+    StreamNotFound {},
+
+    #[error("Cannot delegate to yourself")]
+    SelfDelegation {},
+
+    #[error("Not delegated")]
+    NotDelegated {},
+
+    #[error("Votes must be committed and revealed")]
+    CommitRevealRequired {},
+
+    #[error("Commit-reveal voting is not enabled")]
+    CommitRevealDisabled {},
+
+    #[error("Vote already committed")]
+    AlreadyCommitted {},
+
+    #[error("Commitment not found")]
+    CommitmentNotFound {},
+
+    #[error("Revealed vote does not match the commitment")]
+    CommitmentMismatch {},
+
+    #[error("Proposal not in reveal period")]
+    ProposalNotRevealing {},
+
+    #[error("Reveal period is over")]
+    RevealPeriodOver {},
+
+    #[error("Reveal period is not over yet")]
+    RevealPeriodNotOver {},
+
+    #[error("Voter is not eligible")]
+    VoterNotEligible {},
+
+    #[error("Trust weight must be between 0 and 1")]
+    InvalidTrustWeight {},
+
+    #[error("Already voted on this proposal")]
+    AlreadyVoted {},
+
+    #[error("This voting mode does not accept funds")]
+    FundsNotAccepted {},
+
+    #[error("Commit-reveal ballots only support quadratic voting")]
+    UnsupportedVotingMode {},
+
+    #[error("No voting power at the proposal start height")]
+    NoVotingPower {},
+
+    #[error("Invalid conviction voting parameters")]
+    InvalidConviction {},
+
+    #[error("Conviction voting only takes supporting stake")]
+    ConvictionSupportOnly {},
+
+    #[error("Not a conviction voting proposal")]
+    NotConvictionProposal {},
+
+    #[error("Proposal already exists")]
+    ProposalAlreadyExists {},
+
+    #[error("Ranked and approval ballots are not enabled")]
+    RankingDisabled {},
+
+    #[error("Ballot type does not match the ranking method")]
+    WrongBallotType {},
+
+    #[error("Ballot must list distinct proposals of the round")]
+    InvalidBallot {},
+
+    #[error("Not a retro funding round")]
+    NotRetroRound {},
+
+    #[error("Allocations exceed the round budget")]
+    AllocationExceedsBudget {},
+
+    #[error("Contract is paused")]
+    Paused {},
+
+    #[error("Proposal has no messages to execute")]
+    NothingToExecute {},
+
+    #[error("Proposal already executed")]
+    AlreadyExecuted {},
+
+    #[error("Platform fee above the cap")]
+    FeeTooHigh {},
+
+    #[error("Proposal was not rejected")]
+    ProposalNotRejected {},
+
+    #[error("Nothing to refund")]
+    NothingToRefund {},
+
+    #[error("Reviewer already voted on this milestone")]
+    MilestoneAlreadyReviewed {},
+
+    #[error("Threshold must be below 1")]
+    InvalidThreshold {},
+
+    #[error("Review period is not over yet")]
+    ReviewPeriodNotOver {},
+
+    #[error("Voting period is not over yet")]
+    VotingPeriodNotOver {},
+
+    #[error("Unit price must be above zero")]
+    InvalidUnitPrice {},
+
+    #[error("Cannot migrate from a different contract: {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from a newer version: {previous_version}")]
+    CannotMigrateVersion { previous_version: String },
 }
@@ -1,28 +1,42 @@
 use crate::error::ContractError;
-use cosmwasm_std::Coin;
+use cosmwasm_std::{Addr, BankMsg, Coin, Uint128};
 
+// returns the amount sent above the required one, to be refunded
 pub fn validate_sent_sufficient_coin(
     sent: &[Coin],
     required: Option<Coin>,
-) -> Result<(), ContractError> {
+) -> Result<Uint128, ContractError> {
     if let Some(required_coin) = required {
         let required_amount = required_coin.amount.u128();
-        println!("require amount:{}", required_amount);
         if required_amount > 0 {
-            let sent_sufficient_funds = sent.iter().any(|coin| {
-                // check if a given sent coin matches denom
-                // and has sufficient amount
-                coin.denom == required_coin.denom && coin.amount.u128() == required_amount
-            });
-            
-            return if sent_sufficient_funds {
-                Ok(())
+            if let Some(other) = sent.iter().find(|coin| coin.denom != required_coin.denom) {
+                return Err(ContractError::WrongFundCoin {
+                    expected: required_coin.denom,
+                    got: other.denom.clone(),
+                });
+            }
+            // check if a given sent coin matches denom
+            // and has sufficient amount
+            let sent_amount: Uint128 = sent.iter().map(|coin| coin.amount).sum();
+
+            return if sent_amount >= required_coin.amount {
+                Ok(sent_amount - required_coin.amount)
             } else {
                 Err(ContractError::InsufficientFundsSent {})
             };
         }
     }
-    Ok(())
+    Ok(Uint128::zero())
+}
+
+pub fn refund_excess(recipient: &Addr, excess: Uint128, denom: &str) -> Option<BankMsg> {
+    if excess.is_zero() {
+        return None;
+    }
+    Some(BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: vec![Coin::new(excess.u128(), denom)],
+    })
 }
 
 
@@ -0,0 +1,1070 @@
+use cosmwasm_std::{
+    coins, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env,
+    MessageInfo, Response, StdError, StdResult, Uint128, WasmMsg,
+};
+use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+use cw_storage_plus::Item;
+
+use crate::msg::{
+    EligibleResponse, ExecuteMsg, GetProposalResponse, InstantiateMsg, IsVerifiedResponse,
+    QueryMsg, RegistryQueryMsg,
+};
+use crate::quadratic::QuadraticFundingAlgorithm;
+use crate::state::{
+    ParameterChange, ProposalStatus, State, VoterEligibility, VotingMode, PARAMETER_REVIEW_PERIOD,
+    PARAMETER_VOTING_PERIOD,
+};
+use crate::ContractError;
+
+const VOTING_TOKEN: &str = "voting_token";
+const OWNER: &str = "creator";
+const HUMAN: &str = "human";
+const BOT: &str = "bot";
+const HOLDER: &str = "holder";
+const LATE_HOLDER: &str = "late_holder";
+
+// identity registry that verifies the addresses it was instantiated with
+mod registry {
+    use super::*;
+
+    pub const VERIFIED: Item<Vec<String>> = Item::new("verified");
+
+    pub fn instantiate(
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: Vec<String>,
+    ) -> StdResult<Response> {
+        VERIFIED.save(deps.storage, &msg)?;
+        Ok(Response::new())
+    }
+
+    pub fn execute(
+        _deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        _msg: Empty,
+    ) -> StdResult<Response> {
+        Err(StdError::generic_err("not supported"))
+    }
+
+    pub fn query(deps: Deps, _env: Env, msg: RegistryQueryMsg) -> StdResult<Binary> {
+        match msg {
+            RegistryQueryMsg::IsVerified { address } => to_json_binary(&IsVerifiedResponse {
+                verified: VERIFIED.load(deps.storage)?.contains(&address),
+            }),
+        }
+    }
+}
+
+// staking contract that records every balance change with its block height
+mod snapshot {
+    use super::*;
+    use cosmwasm_schema::cw_serde;
+
+    use crate::msg::{SnapshotBalanceResponse, SnapshotQueryMsg};
+
+    #[cw_serde]
+    pub enum ExecuteMsg {
+        SetBalance { address: String, amount: Uint128 },
+    }
+
+    // (address, height, balance)
+    pub const HISTORY: Item<Vec<(String, u64, Uint128)>> = Item::new("history");
+
+    pub fn instantiate(
+        deps: DepsMut,
+        env: Env,
+        _info: MessageInfo,
+        msg: Vec<(String, Uint128)>,
+    ) -> StdResult<Response> {
+        let history = msg
+            .into_iter()
+            .map(|(address, amount)| (address, env.block.height, amount))
+            .collect();
+        HISTORY.save(deps.storage, &history)?;
+        Ok(Response::new())
+    }
+
+    pub fn execute(
+        deps: DepsMut,
+        env: Env,
+        _info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> StdResult<Response> {
+        match msg {
+            ExecuteMsg::SetBalance { address, amount } => {
+                let mut history = HISTORY.load(deps.storage)?;
+                history.push((address, env.block.height, amount));
+                HISTORY.save(deps.storage, &history)?;
+                Ok(Response::new())
+            }
+        }
+    }
+
+    // like cw-storage-plus snapshots, changes made at `height` are not visible yet
+    pub fn query(deps: Deps, env: Env, msg: SnapshotQueryMsg) -> StdResult<Binary> {
+        match msg {
+            SnapshotQueryMsg::BalanceAtHeight { address, height } => {
+                let height = height.unwrap_or(env.block.height + 1);
+                let balance = HISTORY
+                    .load(deps.storage)?
+                    .into_iter()
+                    .rev()
+                    .find(|(a, h, _)| *a == address && *h < height)
+                    .map(|(_, _, balance)| balance)
+                    .unwrap_or_default();
+                to_json_binary(&SnapshotBalanceResponse { balance })
+            }
+        }
+    }
+}
+
+// cw4 group answering member queries with fixed weights
+mod group {
+    use super::*;
+
+    use cw4::{Cw4QueryMsg, MemberResponse, TotalWeightResponse};
+
+    type Members = Vec<(String, u64)>;
+
+    // member lists with the height they were set at, oldest first
+    pub const MEMBERS: Item<Vec<(u64, Members)>> = Item::new("members");
+
+    pub fn instantiate(
+        deps: DepsMut,
+        env: Env,
+        _info: MessageInfo,
+        msg: Vec<(String, u64)>,
+    ) -> StdResult<Response> {
+        MEMBERS.save(deps.storage, &vec![(env.block.height, msg)])?;
+        Ok(Response::new())
+    }
+
+    // replaces the member list
+    pub fn execute(
+        deps: DepsMut,
+        env: Env,
+        _info: MessageInfo,
+        msg: Vec<(String, u64)>,
+    ) -> StdResult<Response> {
+        let mut members = MEMBERS.load(deps.storage)?;
+        members.push((env.block.height, msg));
+        MEMBERS.save(deps.storage, &members)?;
+        Ok(Response::new())
+    }
+
+    // like cw4-group, a height reads the members as they were before that block
+    fn members_at(deps: Deps, at_height: Option<u64>) -> StdResult<Members> {
+        Ok(MEMBERS
+            .load(deps.storage)?
+            .into_iter()
+            .rev()
+            .find(|(height, _)| at_height.iter().all(|at| height < at))
+            .map(|(_, members)| members)
+            .unwrap_or_default())
+    }
+
+    pub fn query(deps: Deps, _env: Env, msg: Cw4QueryMsg) -> StdResult<Binary> {
+        match msg {
+            Cw4QueryMsg::Member { addr, at_height } => to_json_binary(&MemberResponse {
+                weight: members_at(deps, at_height)?
+                    .into_iter()
+                    .find(|(member, _)| *member == addr)
+                    .map(|(_, weight)| weight),
+            }),
+            Cw4QueryMsg::TotalWeight {} => to_json_binary(&TotalWeightResponse {
+                weight: members_at(deps, None)?
+                    .iter()
+                    .map(|(_, weight)| weight)
+                    .sum(),
+            }),
+            _ => Err(StdError::generic_err("not supported by the mock group")),
+        }
+    }
+}
+
+fn voting_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        crate::contract::execute,
+        crate::contract::instantiate,
+        crate::contract::query,
+    ))
+}
+
+fn registry_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        registry::execute,
+        registry::instantiate,
+        registry::query,
+    ))
+}
+
+fn snapshot_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        snapshot::execute,
+        snapshot::instantiate,
+        snapshot::query,
+    ))
+}
+
+fn group_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        group::execute,
+        group::instantiate,
+        group::query,
+    ))
+}
+
+fn mock_app() -> App {
+    App::new(|router, _, storage| {
+        for voter in [HUMAN, BOT, OWNER] {
+            router
+                .bank
+                .init_balance(
+                    storage,
+                    &Addr::unchecked(voter),
+                    coins(10_000, VOTING_TOKEN),
+                )
+                .unwrap();
+        }
+    })
+}
+
+#[test]
+fn registry_gates_voting() {
+    let mut app = mock_app();
+
+    let voting_id = app.store_code(voting_contract());
+    let voting = app
+        .instantiate_contract(
+            voting_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                denom: VOTING_TOKEN.to_string(),
+            },
+            &[],
+            "voting",
+            None,
+        )
+        .unwrap();
+    let registry_id = app.store_code(registry_contract());
+    let registry = app
+        .instantiate_contract(
+            registry_id,
+            Addr::unchecked(OWNER),
+            &vec![HUMAN.to_string()],
+            &[],
+            "registry",
+            None,
+        )
+        .unwrap();
+
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::UpdateConfig {
+            ballot_mode: None,
+            voter_eligibility: Some(VoterEligibility::Registry { contract: registry }),
+            voting_mode: None,
+            ranking: None,
+        },
+        &[],
+    )
+    .unwrap();
+
+    let msg_vote = ExecuteMsg::Vote {
+        proposal_id: Uint128::from(1u128),
+        yes_vote: true,
+    };
+    let err = app
+        .execute_contract(
+            Addr::unchecked(BOT),
+            voting.clone(),
+            &msg_vote,
+            &coins(1000, VOTING_TOKEN),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::VoterNotEligible {}.to_string()
+    );
+    app.execute_contract(
+        Addr::unchecked(HUMAN),
+        voting.clone(),
+        &msg_vote,
+        &coins(1000, VOTING_TOKEN),
+    )
+    .unwrap();
+
+    let eligible = |address: &str| {
+        let res: EligibleResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &voting,
+                &QueryMsg::Eligible {
+                    address: Addr::unchecked(address),
+                },
+            )
+            .unwrap();
+        res.eligible
+    };
+    assert!(eligible(HUMAN));
+    assert!(!eligible(BOT));
+}
+
+#[test]
+fn snapshot_voting_power() {
+    let mut app = mock_app();
+
+    let voting_id = app.store_code(voting_contract());
+    let voting = app
+        .instantiate_contract(
+            voting_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                denom: VOTING_TOKEN.to_string(),
+            },
+            &[],
+            "voting",
+            None,
+        )
+        .unwrap();
+    let snapshot_id = app.store_code(snapshot_contract());
+    let staking = app
+        .instantiate_contract(
+            snapshot_id,
+            Addr::unchecked(OWNER),
+            &vec![(HOLDER.to_string(), Uint128::from(500u128))],
+            &[],
+            "staking",
+            None,
+        )
+        .unwrap();
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::UpdateConfig {
+            ballot_mode: None,
+            voter_eligibility: None,
+            voting_mode: Some(VotingMode::Snapshot {
+                contract: staking.clone(),
+            }),
+            ranking: None,
+        },
+        &[],
+    )
+    .unwrap();
+    app.update_block(|block| block.height += 1);
+
+    let vote = |app: &mut App, voter: &str, proposal_id: u128| {
+        app.execute_contract(
+            Addr::unchecked(voter),
+            voting.clone(),
+            &ExecuteMsg::Vote {
+                proposal_id: Uint128::from(proposal_id),
+                yes_vote: true,
+            },
+            &[],
+        )
+    };
+    let yes_votes = |app: &App, proposal_id: u128| {
+        let res: GetProposalResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &voting,
+                &QueryMsg::GetProposal {
+                    proposal_id: Uint128::from(proposal_id),
+                },
+            )
+            .unwrap();
+        res.yes_votes
+    };
+
+    // proposals are opened by the owner, not by the first voter
+    let create_round = |app: &mut App, round_id: u128, proposal_id: u128| {
+        app.execute_contract(
+            Addr::unchecked(OWNER),
+            voting.clone(),
+            &ExecuteMsg::CreateRound {
+                round_id: Uint128::from(round_id),
+                proposals: vec![(Uint128::from(proposal_id), "project".to_string())],
+                algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                    negative_votes: false,
+                    cap: None,
+                    eligibility: None,
+                },
+                milestones_required: false,
+                vesting: None,
+                retro: None,
+            },
+            &coins(1000, VOTING_TOKEN),
+        )
+        .unwrap();
+    };
+    let err = vote(&mut app, HOLDER, 1).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::ProposalNotFound {}.to_string()
+    );
+    create_round(&mut app, 1, 1);
+    app.update_block(|block| block.height += 1);
+    vote(&mut app, HOLDER, 1).unwrap();
+    assert_eq!(yes_votes(&app, 1), Uint128::from(500u128));
+    let err = vote(&mut app, HOLDER, 1).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::AlreadyVoted {}.to_string()
+    );
+
+    // tokens acquired after the proposal started don't count on it
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        staking,
+        &snapshot::ExecuteMsg::SetBalance {
+            address: LATE_HOLDER.to_string(),
+            amount: Uint128::from(300u128),
+        },
+        &[],
+    )
+    .unwrap();
+    app.update_block(|block| block.height += 1);
+    let err = vote(&mut app, LATE_HOLDER, 1).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::NoVotingPower {}.to_string()
+    );
+    create_round(&mut app, 2, 2);
+    vote(&mut app, LATE_HOLDER, 2).unwrap();
+    assert_eq!(yes_votes(&app, 2), Uint128::from(300u128));
+}
+
+#[test]
+fn weighted_group_review() {
+    let mut app = mock_app();
+
+    let voting_id = app.store_code(voting_contract());
+    let voting = app
+        .instantiate_contract(
+            voting_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                denom: VOTING_TOKEN.to_string(),
+            },
+            &[],
+            "voting",
+            None,
+        )
+        .unwrap();
+    let group_id = app.store_code(group_contract());
+    let group = app
+        .instantiate_contract(
+            group_id,
+            Addr::unchecked(OWNER),
+            &vec![("alice".to_string(), 3u64), ("bob".to_string(), 1u64)],
+            &[],
+            "committee",
+            None,
+        )
+        .unwrap();
+    app.update_block(|block| block.height += 1);
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::UpdateReviewerGroup {
+            group: Some(group.to_string()),
+        },
+        &[],
+    )
+    .unwrap();
+
+    let proposal_id = Uint128::from(1u128);
+    app.execute_contract(
+        Addr::unchecked(HUMAN),
+        voting.clone(),
+        &ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote: true,
+        },
+        &coins(1000, VOTING_TOKEN),
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::EndVote { proposal_id },
+        &[],
+    )
+    .unwrap();
+
+    // paid votes no longer count once the committee reviews
+    let err = app
+        .execute_contract(
+            Addr::unchecked(BOT),
+            voting.clone(),
+            &ExecuteMsg::Vote {
+                proposal_id,
+                yes_vote: false,
+            },
+            &coins(1000, VOTING_TOKEN),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::ProposalNotInProgress {}.to_string()
+    );
+
+    let review = |app: &mut App, reviewer: &str, approved: bool| {
+        app.execute_contract(
+            Addr::unchecked(reviewer),
+            voting.clone(),
+            &ExecuteMsg::Review {
+                proposal_id,
+                approved,
+            },
+            &[],
+        )
+    };
+    // members joining after the review opened are not on the committee
+    app.update_block(|block| block.height += 1);
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        group.clone(),
+        &vec![
+            ("alice".to_string(), 3u64),
+            ("bob".to_string(), 1u64),
+            ("carol".to_string(), 5u64),
+        ],
+        &[],
+    )
+    .unwrap();
+    let err = review(&mut app, "carol", true).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::Unauthorized {}.to_string()
+    );
+    review(&mut app, "bob", false).unwrap();
+    review(&mut app, "alice", true).unwrap();
+    let err = review(&mut app, "alice", true).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::AlreadyVoted {}.to_string()
+    );
+    let err = review(&mut app, BOT, false).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::Unauthorized {}.to_string()
+    );
+
+    let res: GetProposalResponse = app
+        .wrap()
+        .query_wasm_smart(&voting, &QueryMsg::GetProposal { proposal_id })
+        .unwrap();
+    assert_eq!(res.yes_votes, Uint128::from(3u128));
+    assert_eq!(res.no_votes, Uint128::from(1u128));
+
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::EndReview { proposal_id },
+        &[],
+    )
+    .unwrap();
+    let res: GetProposalResponse = app
+        .wrap()
+        .query_wasm_smart(&voting, &QueryMsg::GetProposal { proposal_id })
+        .unwrap();
+    assert_eq!(res.status, ProposalStatus::Passed);
+}
+
+#[test]
+fn group_milestone_approval() {
+    let mut app = mock_app();
+
+    let voting_id = app.store_code(voting_contract());
+    let voting = app
+        .instantiate_contract(
+            voting_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                denom: VOTING_TOKEN.to_string(),
+            },
+            &[],
+            "voting",
+            None,
+        )
+        .unwrap();
+    let group_id = app.store_code(group_contract());
+    let group = app
+        .instantiate_contract(
+            group_id,
+            Addr::unchecked(OWNER),
+            &vec![
+                ("alice".to_string(), 3u64),
+                ("bob".to_string(), 1u64),
+                ("carol".to_string(), 2u64),
+            ],
+            &[],
+            "committee",
+            None,
+        )
+        .unwrap();
+    app.update_block(|block| block.height += 1);
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::UpdateReviewerGroup {
+            group: Some(group.to_string()),
+        },
+        &[],
+    )
+    .unwrap();
+
+    let round_id = Uint128::from(1u128);
+    let proposal_id = Uint128::from(1u128);
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::CreateRound {
+            round_id,
+            proposals: vec![(proposal_id, "project".to_string())],
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes: false,
+                cap: None,
+                eligibility: None,
+            },
+            milestones_required: true,
+            vesting: None,
+            retro: None,
+        },
+        &coins(1000, VOTING_TOKEN),
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked(HUMAN),
+        voting.clone(),
+        &ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote: true,
+        },
+        &coins(1000, VOTING_TOKEN),
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::EndVote { proposal_id },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        voting.clone(),
+        &ExecuteMsg::Review {
+            proposal_id,
+            approved: true,
+        },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::EndReview { proposal_id },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::FundingProposal { round_id },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked("project"),
+        voting.clone(),
+        &ExecuteMsg::SetMilestones {
+            proposal_id,
+            milestones: vec![Decimal::percent(50), Decimal::percent(50)],
+        },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked("project"),
+        voting.clone(),
+        &ExecuteMsg::ClaimGrant { round_id },
+        &[],
+    )
+    .unwrap();
+
+    // a heavy member joining later does not count towards the committee
+    app.update_block(|block| block.height += 1);
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        group.clone(),
+        &vec![
+            ("alice".to_string(), 3u64),
+            ("bob".to_string(), 1u64),
+            ("carol".to_string(), 2u64),
+            ("dave".to_string(), 10u64),
+        ],
+        &[],
+    )
+    .unwrap();
+    let approve = |app: &mut App, reviewer: &str, milestone: u32, approved: bool| {
+        app.execute_contract(
+            Addr::unchecked(reviewer),
+            voting.clone(),
+            &ExecuteMsg::ApproveMilestone {
+                proposal_id,
+                milestone,
+                approved,
+            },
+            &[],
+        )
+    };
+    let err = approve(&mut app, "dave", 0, true).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::Unauthorized {}.to_string()
+    );
+
+    // half of the total weight of 6 is not enough to release a milestone
+    approve(&mut app, "alice", 0, true).unwrap();
+    let err = app
+        .execute_contract(
+            Addr::unchecked("project"),
+            voting.clone(),
+            &ExecuteMsg::ClaimMilestone { proposal_id },
+            &[],
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::NothingToClaim {}.to_string()
+    );
+    approve(&mut app, "bob", 0, true).unwrap();
+    // carol and alice reject the second milestone, leaving 1 of 6 in reach
+    approve(&mut app, "carol", 1, false).unwrap();
+    approve(&mut app, "alice", 1, false).unwrap();
+    let err = approve(&mut app, "bob", 1, true).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::MilestoneNotPending {}.to_string()
+    );
+
+    app.execute_contract(
+        Addr::unchecked("project"),
+        voting.clone(),
+        &ExecuteMsg::ClaimMilestone { proposal_id },
+        &[],
+    )
+    .unwrap();
+    let balance = app.wrap().query_balance("project", VOTING_TOKEN).unwrap();
+    assert!(!balance.amount.is_zero());
+}
+
+#[test]
+fn governed_parameter_change() {
+    let mut app = mock_app();
+
+    let voting_id = app.store_code(voting_contract());
+    let voting = app
+        .instantiate_contract(
+            voting_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                denom: VOTING_TOKEN.to_string(),
+            },
+            &[],
+            "voting",
+            None,
+        )
+        .unwrap();
+    let group_id = app.store_code(group_contract());
+    let group = app
+        .instantiate_contract(
+            group_id,
+            Addr::unchecked(OWNER),
+            &vec![("alice".to_string(), 1u64)],
+            &[],
+            "committee",
+            None,
+        )
+        .unwrap();
+    app.update_block(|block| block.height += 1);
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::UpdateReviewerGroup {
+            group: Some(group.to_string()),
+        },
+        &[],
+    )
+    .unwrap();
+
+    let msg_config = ExecuteMsg::UpdateConfig {
+        ballot_mode: None,
+        voter_eligibility: None,
+        voting_mode: Some(VotingMode::Linear),
+        ranking: None,
+    };
+    let err = app
+        .execute_contract(Addr::unchecked(BOT), voting.clone(), &msg_config, &[])
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::Unauthorized {}.to_string()
+    );
+
+    let changes = vec![
+        ParameterChange::UpdateConfig {
+            ballot_mode: None,
+            voter_eligibility: None,
+            voting_mode: Some(VotingMode::Linear),
+            ranking: None,
+        },
+        ParameterChange::UpdateReviewers {
+            add: vec!["carol".to_string()],
+            remove: vec![],
+        },
+        ParameterChange::UpdateMilestoneThreshold {
+            threshold: Decimal::percent(75),
+        },
+        ParameterChange::UpdateUnitPrice {
+            unit_price: Uint128::from(2000u128),
+        },
+        ParameterChange::Execute {
+            msgs: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: voting.to_string(),
+                msg: to_json_binary(&ExecuteMsg::UpdateReviewers {
+                    add: vec!["dave".to_string()],
+                    remove: vec![],
+                })
+                .unwrap(),
+                funds: vec![],
+            })],
+        },
+    ];
+    let err = app
+        .execute_contract(
+            Addr::unchecked(BOT),
+            voting.clone(),
+            &ExecuteMsg::ProposeParameterChange {
+                proposal_id: Uint128::from(99u128),
+                change: ParameterChange::UpdateMilestoneThreshold {
+                    threshold: Decimal::one(),
+                },
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::InvalidThreshold {}.to_string()
+    );
+    for (i, change) in changes.into_iter().enumerate() {
+        let proposal_id = Uint128::from(i as u128 + 1);
+        app.execute_contract(
+            Addr::unchecked(BOT),
+            voting.clone(),
+            &ExecuteMsg::ProposeParameterChange {
+                proposal_id,
+                change,
+            },
+            &[],
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(HUMAN),
+            voting.clone(),
+            &ExecuteMsg::Vote {
+                proposal_id,
+                yes_vote: true,
+            },
+            &coins(1000, VOTING_TOKEN),
+        )
+        .unwrap();
+        // neither the owner nor anyone else can close the vote or the review early
+        for (msg, period, early) in [
+            (
+                ExecuteMsg::EndVote { proposal_id },
+                PARAMETER_VOTING_PERIOD,
+                ContractError::VotingPeriodNotOver {},
+            ),
+            (
+                ExecuteMsg::EndReview { proposal_id },
+                PARAMETER_REVIEW_PERIOD,
+                ContractError::ReviewPeriodNotOver {},
+            ),
+        ] {
+            let err = app
+                .execute_contract(Addr::unchecked(OWNER), voting.clone(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(err.root_cause().to_string(), early.to_string());
+            app.update_block(|block| block.time = block.time.plus_seconds(period));
+            app.execute_contract(Addr::unchecked(BOT), voting.clone(), &msg, &[])
+                .unwrap();
+            if let ExecuteMsg::EndVote { .. } = msg {
+                app.execute_contract(
+                    Addr::unchecked("alice"),
+                    voting.clone(),
+                    &ExecuteMsg::Review {
+                        proposal_id,
+                        approved: true,
+                    },
+                    &[],
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    // all changes ran as the contract itself
+    let config: State = app
+        .wrap()
+        .query_wasm_smart(&voting, &QueryMsg::Config {})
+        .unwrap();
+    assert_eq!(config.voting_mode, VotingMode::Linear);
+    assert_eq!(
+        config.reviewers,
+        vec![Addr::unchecked("carol"), Addr::unchecked("dave")]
+    );
+    assert_eq!(config.milestone_threshold, Decimal::percent(75));
+    assert_eq!(config.unit_price, Uint128::from(2000u128));
+}
+
+#[test]
+fn execute_passed_proposal() {
+    let mut app = mock_app();
+
+    let voting_id = app.store_code(voting_contract());
+    let voting = app
+        .instantiate_contract(
+            voting_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                denom: VOTING_TOKEN.to_string(),
+            },
+            &[],
+            "voting",
+            None,
+        )
+        .unwrap();
+    let group_id = app.store_code(group_contract());
+    let group = app
+        .instantiate_contract(
+            group_id,
+            Addr::unchecked(OWNER),
+            &vec![("alice".to_string(), 1u64)],
+            &[],
+            "committee",
+            None,
+        )
+        .unwrap();
+    app.update_block(|block| block.height += 1);
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::UpdateReviewerGroup {
+            group: Some(group.to_string()),
+        },
+        &[],
+    )
+    .unwrap();
+
+    let proposal_id = Uint128::from(1u128);
+    app.execute_contract(
+        Addr::unchecked("grantee"),
+        voting.clone(),
+        &ExecuteMsg::Propose {
+            proposal_id,
+            fund_address: "grantee".to_string(),
+            msgs: vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: "grantee".to_string(),
+                amount: coins(500, VOTING_TOKEN),
+            })],
+        },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked(HUMAN),
+        voting.clone(),
+        &ExecuteMsg::Vote {
+            proposal_id,
+            yes_vote: true,
+        },
+        &coins(1000, VOTING_TOKEN),
+    )
+    .unwrap();
+
+    let execute = |app: &mut App| {
+        app.execute_contract(
+            Addr::unchecked(BOT),
+            voting.clone(),
+            &ExecuteMsg::ExecuteProposal { proposal_id },
+            &[],
+        )
+    };
+    let err = execute(&mut app).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::ProposalNotPassed {}.to_string()
+    );
+
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::EndVote { proposal_id },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        voting.clone(),
+        &ExecuteMsg::Review {
+            proposal_id,
+            approved: true,
+        },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        Addr::unchecked(OWNER),
+        voting.clone(),
+        &ExecuteMsg::EndReview { proposal_id },
+        &[],
+    )
+    .unwrap();
+
+    // passed proposals wait for the pause to be lifted
+    let set_paused = |app: &mut App, msg: ExecuteMsg| {
+        app.execute_contract(Addr::unchecked(OWNER), voting.clone(), &msg, &[])
+            .unwrap();
+    };
+    set_paused(&mut app, ExecuteMsg::Pause {});
+    let err = execute(&mut app).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::Paused {}.to_string()
+    );
+    set_paused(&mut app, ExecuteMsg::Unpause {});
+
+    execute(&mut app).unwrap();
+    let balance = app.wrap().query_balance("grantee", VOTING_TOKEN).unwrap();
+    assert_eq!(balance.amount, Uint128::from(500u128));
+    let res: GetProposalResponse = app
+        .wrap()
+        .query_wasm_smart(&voting, &QueryMsg::GetProposal { proposal_id })
+        .unwrap();
+    assert_eq!(res.status, ProposalStatus::Executed);
+
+    let err = execute(&mut app).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::AlreadyExecuted {}.to_string()
+    );
+}
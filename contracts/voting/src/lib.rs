@@ -4,4 +4,8 @@ pub mod msg;
 pub mod state;
 pub mod helpers;
 pub mod quadratic;
+pub mod ranking;
+pub mod retro;
+#[cfg(test)]
+mod integration_tests;
 pub use crate::error::ContractError;
@@ -1,19 +1,184 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use crate::state::{ProposalStatus, State};
-use cosmwasm_std::{Uint128, Addr};
+use crate::quadratic::{CalculatedGrant, QuadraticFundingAlgorithm};
+use crate::ranking::RankingMethod;
+use crate::state::{
+    BallotMode, Distribution, Escrow, ParameterChange, PlatformFee, ProposalStatus, Ranking, RetroFunding,
+    State, Stream, Vesting, VoterEligibility, VotingMode,
+};
+use cosmwasm_std::{Uint128, Addr, Binary, CosmosMsg, Decimal, Int128};
+use cw3::{ProposalListResponse, ProposalResponse, VoteListResponse, VoteResponse};
+use cw_utils::ThresholdResponse;
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub denom:String
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
 #[cw_serde]
 pub enum ExecuteMsg {
     Vote {proposal_id: Uint128, yes_vote: bool},
     Review {proposal_id: Uint128, approved: bool},
     EndVote{proposal_id: Uint128},
+    // owner only, except for parameter changes once their review period ran out
     EndReview{proposal_id: Uint128},
+    // computes and stores the round's payouts, grantees pull them with ClaimGrant
     FundingProposal {
+        round_id: Uint128,
+    },
+    ClaimGrant {
+        round_id: Uint128,
+    },
+    // budget is the coin sent with the message, proposals map id to fund address
+    CreateRound {
+        round_id: Uint128,
+        proposals: Vec<(Uint128, String)>,
+        algorithm: QuadraticFundingAlgorithm,
+        milestones_required: bool,
+        vesting: Option<Vesting>,
+        // completed projects paid by badgeholder allocations, the algorithm is unused
+        retro: Option<RetroFunding>,
+    },
+    UpdateReviewers {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    // none goes back to the staked reviews
+    UpdateReviewerGroup {
+        group: Option<String>,
+    },
+    // declared by the fund address of a passed proposal, percentages sum to 1
+    SetMilestones {
+        proposal_id: Uint128,
+        milestones: Vec<Decimal>,
+    },
+    // released once the approving weight exceeds the milestone threshold, rejected
+    // once the remaining reviewers cannot reach it anymore; with a reviewer group
+    // the members are weighed as of the proposal's review
+    ApproveMilestone {
+        proposal_id: Uint128,
+        milestone: u32,
+        approved: bool,
+    },
+    UpdateMilestoneThreshold {
+        threshold: Decimal,
+    },
+    UpdateUnitPrice {
+        unit_price: Uint128,
+    },
+    ClaimMilestone {
+        proposal_id: Uint128,
+    },
+    // returns every tranche that was not claimed yet to the matching pool
+    CloseEscrow {
+        proposal_id: Uint128,
+    },
+    ClaimVested {
+        proposal_id: Uint128,
+    },
+    // stops the stream, unvested funds go to the round leftover (see WithdrawLeftover)
+    ClawBack {
+        proposal_id: Uint128,
+    },
+    // the delegate's votes also count for the sender until they vote themselves,
+    // in quadratic mode the coins sent are credits paying for those votes
+    Delegate {
+        to: String,
+    },
+    Undelegate {},
+    // fields left empty keep their current value
+    UpdateConfig {
+        ballot_mode: Option<BallotMode>,
+        voter_eligibility: Option<VoterEligibility>,
+        voting_mode: Option<VotingMode>,
+        ranking: Option<Ranking>,
+    },
+    UpdateAllowlist {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    // down-weights contributors in the match instead of excluding them
+    SetTrustWeights {
+        weights: Vec<(String, Decimal)>,
+        // replaces the weight of unverified contributors when set
+        default: Option<Decimal>,
+    },
+    // commitment is sha256("{voter}:{proposal_id}:{votes}:{yes}:{salt}"),
+    // the coins sent are a deposit covering the quadratic cost of the votes
+    CommitVote {
+        proposal_id: Uint128,
+        commitment: Binary,
+    },
+    RevealVote {
+        proposal_id: Uint128,
+        votes: u64,
+        yes: bool,
+        salt: String,
+    },
+    // conviction voting, staked with Vote and paid from the pool once passed
+    ProposeFunding {
+        proposal_id: Uint128,
+        beneficiary: String,
+        requested: Uint128,
+    },
+    FundConvictionPool {},
+    UpdateConviction {
+        proposal_id: Uint128,
+    },
+    WithdrawStake {
+        proposal_id: Uint128,
+    },
+    // ballots over every proposal of a round, resubmitting replaces the previous one
+    ApprovalBallot {
+        round_id: Uint128,
+        approved: Vec<Uint128>,
+    },
+    RankedBallot {
+        round_id: Uint128,
+        ranking: Vec<Uint128>,
+    },
+    // a badgeholder's split of a retro round's budget, resubmitting replaces it
+    AllocationBallot {
+        round_id: Uint128,
+        allocations: Vec<(Uint128, Uint128)>,
+    },
+    // halts everything but withdrawals, refunds and the guardian settings
+    Pause {},
+    Unpause {},
+    // can pause and unpause next to the owner
+    UpdateGuardian {
+        guardian: Option<String>,
+    },
+    // voted and reviewed like any proposal, the change runs when it passes
+    // unless the contract is paused
+    ProposeParameterChange {
+        proposal_id: Uint128,
+        change: ParameterChange,
+    },
+    // the messages run as the contract, triggered by anyone once the proposal passed
+    Propose {
+        proposal_id: Uint128,
+        fund_address: String,
+        msgs: Vec<CosmosMsg>,
+    },
+    ExecuteProposal {
+        proposal_id: Uint128,
+    },
+    UpdateFee {
+        fee: Option<PlatformFee>,
+    },
+    // sends the collected fees to the treasury address
+    WithdrawFees {},
+    // pays out what a distributed round did not allocate, plus what milestones,
+    // claw backs and no-votes returned to it
+    WithdrawLeftover {
+        round_id: Uint128,
+        recipient: String,
+    },
+    // gives the sender back what they staked on a rejected proposal
+    RefundContribution {
         proposal_id: Uint128,
     },
 
@@ -29,6 +194,51 @@ pub enum QueryMsg {
     GetProposal {proposal_id: Uint128},
     #[returns(TokenStakeResponse)]
     TokenStake {address: Addr},
+    #[returns(EstimatedMatchesResponse)]
+    EstimatedMatches {round_id: Uint128},
+    #[returns(EstimatedMatchResponse)]
+    EstimatedMatch {proposal_id: Uint128},
+    // contributor is optional, without it the amount counts as a new contributor
+    #[returns(SimulateContributionResponse)]
+    SimulateContribution {
+        proposal_id: Uint128,
+        amount: Uint128,
+        contributor: Option<Addr>,
+    },
+    #[returns(Distribution)]
+    Distribution {round_id: Uint128},
+    #[returns(Escrow)]
+    Escrow {proposal_id: Uint128},
+    #[returns(StreamResponse)]
+    Stream {proposal_id: Uint128},
+    #[returns(DelegationResponse)]
+    Delegation {address: Addr},
+    #[returns(EligibleResponse)]
+    Eligible {address: Addr},
+    #[returns(ConvictionResponse)]
+    Conviction {proposal_id: Uint128},
+    #[returns(RoundResultsResponse)]
+    RoundResults {round_id: Uint128},
+    // cw3 interface, see the Cw3 response types below
+    #[returns(ProposalResponse)]
+    Proposal {proposal_id: u64},
+    #[returns(ProposalListResponse)]
+    ListProposals {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(VoteResponse)]
+    Vote {proposal_id: u64, voter: String},
+    #[returns(VoteListResponse)]
+    ListVotes {
+        proposal_id: u64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    #[returns(ThresholdResponse)]
+    Threshold {},
+    #[returns(TreasuryResponse)]
+    Treasury {},
 }
 
 
@@ -39,9 +249,103 @@ pub struct GetProposalResponse {
     pub no_votes: Uint128,
 }
 
+#[cw_serde]
+pub struct TreasuryResponse {
+    pub fee: Option<PlatformFee>,
+    pub balance: Uint128,
+}
+
 #[cw_serde]
 pub struct TokenStakeResponse {
     pub token_balance: Uint128,
 }
 
+#[cw_serde]
+pub struct EstimatedMatchesResponse {
+    // matches are net of the platform fee
+    pub grants: Vec<CalculatedGrant>,
+    // fee on each grant's match, in the order of the grants
+    pub fees: Vec<Uint128>,
+    pub leftover: Uint128,
+}
+
+#[cw_serde]
+pub struct EstimatedMatchResponse {
+    pub round_id: Uint128,
+    pub grant: CalculatedGrant,
+    pub fee: Uint128,
+}
+
+#[cw_serde]
+pub struct SimulateContributionResponse {
+    pub current_match: Uint128,
+    pub simulated_match: Uint128,
+    // negative when the contribution lowers the proposal's match
+    pub match_change: Int128,
+}
+
+#[cw_serde]
+pub struct StreamResponse {
+    pub stream: Stream,
+    // vested at the current block time, including what was claimed
+    pub vested: Uint128,
+}
+
+#[cw_serde]
+pub struct DelegationResponse {
+    // who votes for the address
+    pub delegate: Option<Addr>,
+    // who the address votes for
+    pub delegators: Vec<Addr>,
+    // left to pay for votes cast by the delegate
+    pub credits: Uint128,
+}
+
+#[cw_serde]
+pub struct ConvictionResponse {
+    pub requested: Uint128,
+    pub staked: Uint128,
+    pub conviction: Uint128,
+    // none while the request is not below the pool
+    pub required: Option<Uint128>,
+    pub pool: Uint128,
+}
+
+#[cw_serde]
+pub struct RoundResultsResponse {
+    pub method: RankingMethod,
+    pub winners: Vec<Uint128>,
+    pub scores: Vec<(Uint128, u64)>,
+    pub ballots: u64,
+}
+
+#[cw_serde]
+pub struct EligibleResponse {
+    pub eligible: bool,
+}
+
+// interface expected from the identity registry in VoterEligibility::Registry
+#[cw_serde]
+pub enum RegistryQueryMsg {
+    IsVerified {address: String},
+}
+
+#[cw_serde]
+pub struct IsVerifiedResponse {
+    pub verified: bool,
+}
+
+// interface expected from the staking or cw20 contract in VotingMode::Snapshot
+#[cw_serde]
+pub enum SnapshotQueryMsg {
+    BalanceAtHeight {
+        address: String,
+        height: Option<u64>,
+    },
+}
+
+#[cw_serde]
+pub struct SnapshotBalanceResponse {
+    pub balance: Uint128,
+}
 
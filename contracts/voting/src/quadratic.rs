@@ -1,19 +1,157 @@
-
 use crate::error::ContractError;
 
 use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Decimal, Uint128};
 
 use integer_sqrt::IntegerSquareRoot;
 
 #[cw_serde]
 pub enum QuadraticFundingAlgorithm {
-    CapitalConstrainedLiberalRadicalism { parameter: String },
+    CapitalConstrainedLiberalRadicalism {
+        // ceiling on a single project's match, excess is redistributed
+        #[serde(default)]
+        cap: Option<MatchingCap>,
+        // projects failing these still get their collected funds, but no match
+        #[serde(default)]
+        eligibility: Option<EligibilityRules>,
+        // no-votes subtract their square roots from the match instead of adding to it
+        #[serde(default)]
+        negative_votes: bool,
+    },
+}
+
+impl QuadraticFundingAlgorithm {
+    pub fn negative_votes(&self) -> bool {
+        match self {
+            Self::CapitalConstrainedLiberalRadicalism { negative_votes, .. } => *negative_votes,
+        }
+    }
+
+    pub fn calculate(
+        &self,
+        grants: Vec<RawGrant>,
+        budget: Option<u128>,
+    ) -> Result<(Vec<CalculatedGrant>, LeftOver), ContractError> {
+        match self {
+            Self::CapitalConstrainedLiberalRadicalism {
+                cap,
+                eligibility,
+                negative_votes,
+            } => {
+                let grants = if *negative_votes {
+                    grants
+                } else {
+                    grants
+                        .into_iter()
+                        .map(|mut g| {
+                            g.negative_funds.clear();
+                            g.negative_weights.clear();
+                            g
+                        })
+                        .collect()
+                };
+
+                let rules = match eligibility {
+                    Some(rules) => rules,
+                    None => return calculate_clr_with_cap(grants, budget, cap.as_ref()),
+                };
+
+                let checked: Vec<_> = grants
+                    .into_iter()
+                    .map(|g| {
+                        let reasons = rules.check(&g);
+                        (g, reasons)
+                    })
+                    .collect();
+                let eligible = checked
+                    .iter()
+                    .filter(|(_, reasons)| reasons.is_empty())
+                    .map(|(g, _)| g.clone())
+                    .collect();
+                let (matched, leftover) = calculate_clr_with_cap(eligible, budget, cap.as_ref())?;
+
+                // put ineligible projects back in their original position
+                let mut matched_iter = matched.into_iter();
+                let calculated = checked
+                    .into_iter()
+                    .map(|(g, reasons)| {
+                        if reasons.is_empty() {
+                            matched_iter.next().unwrap()
+                        } else {
+                            CalculatedGrant {
+                                addr: g.addr,
+                                grant: 0,
+                                collected_vote_funds: g.collected_vote_funds,
+                                ineligible_reasons: reasons,
+                            }
+                        }
+                    })
+                    .collect();
+                Ok((calculated, leftover))
+            }
+        }
+    }
+}
+
+#[cw_serde]
+pub struct EligibilityRules {
+    pub min_contributors: u64,
+    pub min_total_contributions: Uint128,
+}
+
+impl EligibilityRules {
+    // each entry of `funds` is one contributor's total, zero entries don't count
+    pub fn check(&self, grant: &RawGrant) -> Vec<IneligibilityReason> {
+        let mut reasons = vec![];
+        let contributors = grant.funds.iter().filter(|f| **f > 0).count() as u64;
+        if contributors < self.min_contributors {
+            reasons.push(IneligibilityReason::TooFewContributors {
+                required: self.min_contributors,
+                got: contributors,
+            });
+        }
+        let total: u128 = grant.funds.iter().sum();
+        if total < self.min_total_contributions.u128() {
+            reasons.push(IneligibilityReason::TooLittleContributed {
+                required: self.min_total_contributions,
+                got: Uint128::from(total),
+            });
+        }
+        reasons
+    }
+}
+
+#[cw_serde]
+pub enum IneligibilityReason {
+    TooFewContributors { required: u64, got: u64 },
+    TooLittleContributed { required: Uint128, got: Uint128 },
+}
+
+#[cw_serde]
+pub enum MatchingCap {
+    Absolute { amount: Uint128 },
+    // share of the matching pool, e.g. 0.25 for 25%
+    Percentage { ratio: Decimal },
+}
+
+impl MatchingCap {
+    fn amount(&self, budget: u128) -> u128 {
+        match self {
+            MatchingCap::Absolute { amount } => amount.u128(),
+            MatchingCap::Percentage { ratio } => (Uint128::from(budget) * *ratio).u128(),
+        }
+    }
 }
 
 #[cw_serde]
 pub struct RawGrant {
     pub addr: String,
     pub funds: Vec<u128>,
+    // trust weight of each contributor in `funds` (0..=1), missing entries count as 1
+    pub weights: Vec<Decimal>,
+    // no-vote contributions and their trust weights, only used with negative votes
+    pub negative_funds: Vec<u128>,
+    pub negative_weights: Vec<Decimal>,
     pub collected_vote_funds: u128,
 }
 
@@ -22,6 +160,9 @@ pub struct CalculatedGrant {
     pub addr: String,
     pub grant: u128,
     pub collected_vote_funds: u128,
+    // empty when the project was eligible for matching
+    #[serde(default)]
+    pub ineligible_reasons: Vec<IneligibilityReason>,
 }
 
 type LeftOver = u128;
@@ -30,13 +171,28 @@ pub fn calculate_clr(
     grants: Vec<RawGrant>,
     budget: Option<u128>,
 ) -> Result<(Vec<CalculatedGrant>, LeftOver), ContractError> {
+    calculate_clr_with_cap(grants, budget, None)
+}
+
+pub fn calculate_clr_with_cap(
+    grants: Vec<RawGrant>,
+    budget: Option<u128>,
+    cap: Option<&MatchingCap>,
+) -> Result<(Vec<CalculatedGrant>, LeftOver), ContractError> {
+    if grants
+        .iter()
+        .any(|g| g.weights.iter().chain(&g.negative_weights).any(|w| *w > Decimal::one()))
+    {
+        return Err(ContractError::InvalidTrustWeight {});
+    }
+
     // clr algorithm works with budget constrain
     if let Some(budget) = budget {
         // calculate matches sum
         let matched = calculate_matched_sum(grants);
 
         // constraint the grants by budget
-        let constrained = constrain_by_budget(matched, budget);
+        let constrained = constrain_by_budget(matched, budget, cap.map(|c| c.amount(budget)));
 
         let constrained_sum: u128 = constrained.iter().map(|c| c.grant).sum();
         // calculate leftover
@@ -50,30 +206,89 @@ pub fn calculate_clr(
     }
 }
 
-// takes square root of each fund, sums, then squares and returns u128
+// takes square root of each fund scaled by its trust weight, sums, subtracts the
+// negative funds summed the same way, floors at zero, then squares and returns u128
 fn calculate_matched_sum(grants: Vec<RawGrant>) -> Vec<CalculatedGrant> {
     grants
         .into_iter()
         .map(|g| {
-            let sum_sqrts: u128 = g.funds.into_iter().map(|v| v.integer_sqrt()).sum();
+            let net = weighted_sqrt_sum(&g.funds, &g.weights) as i128
+                - weighted_sqrt_sum(&g.negative_funds, &g.negative_weights) as i128;
+            let sum_sqrts = net.max(0) as u128;
             CalculatedGrant {
                 addr: g.addr,
                 grant: sum_sqrts * sum_sqrts,
                 collected_vote_funds: g.collected_vote_funds,
+                ineligible_reasons: vec![],
             }
         })
         .collect()
 }
 
-// takes square root of each fund, sums, then squares and returns u128
-fn constrain_by_budget(grants: Vec<CalculatedGrant>, budget: u128) -> Vec<CalculatedGrant> {
-    let raw_total: u128 = grants.iter().map(|g| g.grant).sum();
+fn weighted_sqrt_sum(funds: &[u128], weights: &[Decimal]) -> u128 {
+    funds
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let weight = weights.get(i).copied().unwrap_or_else(Decimal::one);
+            (Uint128::from(v.integer_sqrt()) * weight).u128()
+        })
+        .sum()
+}
+
+// scales matched sums to the budget. with a cap, grants above it are pinned
+// to the cap and the remaining budget is spread again over the uncapped ones
+// until no share exceeds the cap.
+fn constrain_by_budget(
+    grants: Vec<CalculatedGrant>,
+    budget: u128,
+    cap: Option<u128>,
+) -> Vec<CalculatedGrant> {
+    let mut capped = vec![false; grants.len()];
+    let mut remaining = budget;
+    loop {
+        let raw_total: u128 = grants
+            .iter()
+            .zip(&capped)
+            .filter(|(_, c)| !**c)
+            .map(|(g, _)| g.grant)
+            .sum();
+        let cap = match cap {
+            Some(cap) if raw_total > 0 => cap,
+            _ => break,
+        };
+        let available = remaining;
+        let mut newly_capped = false;
+        for (g, c) in grants.iter().zip(capped.iter_mut()) {
+            if !*c && (g.grant * available) / raw_total > cap {
+                *c = true;
+                remaining -= cap;
+                newly_capped = true;
+            }
+        }
+        if !newly_capped {
+            break;
+        }
+    }
+
+    let raw_total: u128 = grants
+        .iter()
+        .zip(&capped)
+        .filter(|(_, c)| !**c)
+        .map(|(g, _)| g.grant)
+        .sum();
     grants
         .into_iter()
-        .map(|g| CalculatedGrant {
+        .zip(capped)
+        .map(|(g, c)| CalculatedGrant {
             addr: g.addr,
-            grant: (g.grant * budget) / raw_total,
+            grant: match (c, cap) {
+                (true, Some(cap)) => cap,
+                _ if raw_total == 0 => 0,
+                _ => (g.grant * remaining) / raw_total,
+            },
             collected_vote_funds: g.collected_vote_funds,
+            ineligible_reasons: g.ineligible_reasons,
         })
         .collect()
 }
@@ -112,21 +327,33 @@ mod tests {
             RawGrant {
                 addr: proposal1.fund_address.clone(),
                 funds: votes1.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes1.iter().sum(),
             },
             RawGrant {
                 addr: proposal2.fund_address.clone(),
                 funds: votes2.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes2.iter().sum(),
             },
             RawGrant {
                 addr: proposal3.fund_address.clone(),
                 funds: votes3.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes3.iter().sum(),
             },
             RawGrant {
                 addr: proposal4.fund_address.clone(),
                 funds: votes4.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes4.iter().sum(),
             },
         ];
@@ -135,21 +362,25 @@ mod tests {
                 addr: proposal1.fund_address,
                 grant: 84737u128,
                 collected_vote_funds: 7200u128,
+                ineligible_reasons: vec![],
             },
             CalculatedGrant {
                 addr: proposal2.fund_address,
                 grant: 147966u128,
                 collected_vote_funds: 12345u128,
+                ineligible_reasons: vec![],
             },
             CalculatedGrant {
                 addr: proposal3.fund_address,
                 grant: 52312u128,
                 collected_vote_funds: 4456u128,
+                ineligible_reasons: vec![],
             },
             CalculatedGrant {
                 addr: proposal4.fund_address,
                 grant: 714983u128,
                 collected_vote_funds: 60000u128,
+                ineligible_reasons: vec![],
             },
         ];
         let res = calculate_clr(grants, Some(1000000u128));
@@ -195,21 +426,33 @@ mod tests {
             RawGrant {
                 addr: proposal1.fund_address.clone(),
                 funds: votes1.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes1.iter().sum(),
             },
             RawGrant {
                 addr: proposal2.fund_address.clone(),
                 funds: votes2.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes2.iter().sum(),
             },
             RawGrant {
                 addr: proposal3.fund_address.clone(),
                 funds: votes3.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes3.iter().sum(),
             },
             RawGrant {
                 addr: proposal4.fund_address.clone(),
                 funds: votes4.clone(),
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
                 collected_vote_funds: votes4.iter().sum(),
             },
         ];
@@ -218,21 +461,25 @@ mod tests {
                 addr: proposal1.fund_address,
                 grant: 60212u128,
                 collected_vote_funds: votes1.iter().sum(),
+                ineligible_reasons: vec![],
             },
             CalculatedGrant {
                 addr: proposal2.fund_address,
                 grant: 164602u128,
                 collected_vote_funds: votes2.iter().sum(),
+                ineligible_reasons: vec![],
             },
             CalculatedGrant {
                 addr: proposal3.fund_address,
                 grant: 228537u128,
                 collected_vote_funds: votes3.iter().sum(),
+                ineligible_reasons: vec![],
             },
             CalculatedGrant {
                 addr: proposal4.fund_address,
                 grant: 96648u128,
                 collected_vote_funds: votes4.iter().sum(),
+                ineligible_reasons: vec![],
             },
         ];
         let res = calculate_clr(grants, Some(550000u128));
@@ -244,4 +491,244 @@ mod tests {
             e => panic!("unexpected error, got {:?}", e),
         }
     }
-}
\ No newline at end of file
+
+    fn single_vote_grants() -> Vec<RawGrant> {
+        vec![7200u128, 12345u128, 4456u128, 60000u128]
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| RawGrant {
+                addr: format!("proposal{}", i + 1),
+                funds: vec![v],
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: v,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_clr_percentage_cap() {
+        let algorithm = QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+            negative_votes: false,
+            eligibility: None,
+            cap: Some(MatchingCap::Percentage {
+                ratio: Decimal::percent(30),
+            }),
+        };
+        let (grants, leftover) = algorithm
+            .calculate(single_vote_grants(), Some(1000000u128))
+            .unwrap();
+        let got: Vec<u128> = grants.iter().map(|g| g.grant).collect();
+        // proposal4 is capped first, then proposal2 once its share grows
+        assert_eq!(got, vec![247318u128, 300000u128, 152681u128, 300000u128]);
+        assert_eq!(leftover, 1);
+    }
+
+    #[test]
+    fn test_clr_absolute_cap() {
+        let algorithm = QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+            negative_votes: false,
+            eligibility: None,
+            cap: Some(MatchingCap::Absolute {
+                amount: Uint128::from(100000u128),
+            }),
+        };
+        let (grants, leftover) = algorithm
+            .calculate(single_vote_grants(), Some(1000000u128))
+            .unwrap();
+        // every project hits the cap, the rest stays in the pool
+        assert!(grants.iter().all(|g| g.grant == 100000u128));
+        assert_eq!(leftover, 600000);
+    }
+
+    #[test]
+    fn test_clr_cap_not_reached() {
+        let algorithm = QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+            negative_votes: false,
+            eligibility: None,
+            cap: Some(MatchingCap::Percentage {
+                ratio: Decimal::percent(80),
+            }),
+        };
+        let capped = algorithm
+            .calculate(single_vote_grants(), Some(1000000u128))
+            .unwrap();
+        let uncapped = calculate_clr(single_vote_grants(), Some(1000000u128)).unwrap();
+        assert_eq!(capped, uncapped);
+    }
+
+    #[test]
+    fn test_clr_eligibility() {
+        let algorithm = QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+            negative_votes: false,
+            cap: None,
+            eligibility: Some(EligibilityRules {
+                min_contributors: 2,
+                min_total_contributions: Uint128::from(1000u128),
+            }),
+        };
+        let grants = vec![
+            RawGrant {
+                addr: "proposal1".to_string(),
+                funds: vec![1200u128, 44999u128, 33u128],
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: 46232u128,
+            },
+            RawGrant {
+                addr: "sybil".to_string(),
+                funds: vec![90000u128],
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: 90000u128,
+            },
+            RawGrant {
+                addr: "dust".to_string(),
+                funds: vec![100u128, 5u128, 0u128],
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: 105u128,
+            },
+            RawGrant {
+                addr: "proposal2".to_string(),
+                funds: vec![30000u128, 58999u128],
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: 88999u128,
+            },
+        ];
+        let (calculated, leftover) = algorithm.calculate(grants, Some(550000u128)).unwrap();
+
+        // same split as a round with only the two eligible projects
+        let (expected, expected_leftover) = calculate_clr(
+            vec![
+                RawGrant {
+                    addr: "proposal1".to_string(),
+                    funds: vec![1200u128, 44999u128, 33u128],
+                    weights: vec![],
+                    negative_funds: vec![],
+                    negative_weights: vec![],
+                    collected_vote_funds: 46232u128,
+                },
+                RawGrant {
+                    addr: "proposal2".to_string(),
+                    funds: vec![30000u128, 58999u128],
+                    weights: vec![],
+                    negative_funds: vec![],
+                    negative_weights: vec![],
+                    collected_vote_funds: 88999u128,
+                },
+            ],
+            Some(550000u128),
+        )
+        .unwrap();
+        assert_eq!(calculated[0], expected[0]);
+        assert_eq!(calculated[3], expected[1]);
+        assert_eq!(leftover, expected_leftover);
+
+        assert_eq!(calculated[1].grant, 0);
+        assert_eq!(calculated[1].collected_vote_funds, 90000u128);
+        assert_eq!(
+            calculated[1].ineligible_reasons,
+            vec![IneligibilityReason::TooFewContributors {
+                required: 2,
+                got: 1
+            }]
+        );
+        assert_eq!(calculated[2].grant, 0);
+        assert_eq!(
+            calculated[2].ineligible_reasons,
+            vec![IneligibilityReason::TooLittleContributed {
+                required: Uint128::from(1000u128),
+                got: Uint128::from(105u128)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_clr_trust_weights() {
+        let grants = vec![
+            RawGrant {
+                addr: "verified".to_string(),
+                funds: vec![10000u128, 10000u128],
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: 20000u128,
+            },
+            RawGrant {
+                addr: "half_verified".to_string(),
+                funds: vec![10000u128, 10000u128],
+                weights: vec![Decimal::one(), Decimal::zero()],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: 20000u128,
+            },
+        ];
+        // (100 + 100)^2 against (100 + 0)^2
+        let (calculated, leftover) = calculate_clr(grants.clone(), Some(50000u128)).unwrap();
+        assert_eq!(calculated[0].grant, 40000u128);
+        assert_eq!(calculated[1].grant, 10000u128);
+        assert_eq!(leftover, 0);
+
+        let mut grants = grants;
+        grants[1].weights[1] = Decimal::percent(150);
+        let err = calculate_clr(grants, Some(50000u128)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTrustWeight {}));
+    }
+
+    #[test]
+    fn test_clr_negative_votes() {
+        let algorithm =
+            |negative_votes| QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                negative_votes,
+                cap: None,
+                eligibility: None,
+            };
+        let grants = vec![
+            RawGrant {
+                addr: "contested".to_string(),
+                funds: vec![10000u128, 2500u128],
+                weights: vec![],
+                negative_funds: vec![400u128],
+                negative_weights: vec![],
+                collected_vote_funds: 12500u128,
+            },
+            RawGrant {
+                addr: "collusive".to_string(),
+                funds: vec![900u128],
+                weights: vec![],
+                negative_funds: vec![1600u128, 10000u128],
+                negative_weights: vec![Decimal::one(), Decimal::percent(50)],
+                collected_vote_funds: 900u128,
+            },
+            RawGrant {
+                addr: "honest".to_string(),
+                funds: vec![10000u128],
+                weights: vec![],
+                negative_funds: vec![],
+                negative_weights: vec![],
+                collected_vote_funds: 10000u128,
+            },
+        ];
+
+        // (100 + 50 - 20)^2, (30 - 40 - 50) floored to 0, 100^2
+        let (calculated, leftover) = algorithm(true)
+            .calculate(grants.clone(), Some(26900u128))
+            .unwrap();
+        assert_eq!(calculated[0].grant, 16900u128);
+        assert_eq!(calculated[1].grant, 0);
+        assert_eq!(calculated[1].collected_vote_funds, 900u128);
+        assert_eq!(calculated[2].grant, 10000u128);
+        assert_eq!(leftover, 0);
+
+        // without the mode no-votes are ignored
+        let (calculated, _) = algorithm(false).calculate(grants, Some(26900u128)).unwrap();
+        assert_eq!(calculated[1].grant, 26900u128 * 900 / (22500 + 900 + 10000));
+    }
+}
@@ -0,0 +1,188 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Uint128;
+
+#[cw_serde]
+#[derive(Eq)]
+pub enum RankingMethod {
+    // one point for every approved proposal, takes ApprovalBallot
+    Approval,
+    // with n candidates a first preference is worth n - 1 points, the last 0
+    Borda,
+    // instant-runoff, run again without the previous winners to fill every seat
+    InstantRunoff,
+}
+
+#[cw_serde]
+pub struct RankingResult {
+    // best first
+    pub winners: Vec<Uint128>,
+    // points for approval and borda, first preferences for instant-runoff
+    pub scores: Vec<(Uint128, u64)>,
+}
+
+impl RankingMethod {
+    // ballots list proposal ids, approved ones or in order of preference.
+    // ids that are not candidates are skipped
+    pub fn tally(
+        &self,
+        candidates: &[Uint128],
+        ballots: &[Vec<Uint128>],
+        seats: usize,
+    ) -> RankingResult {
+        let seats = seats.min(candidates.len());
+        match self {
+            Self::Approval => by_score(approval_scores(candidates, ballots), seats),
+            Self::Borda => by_score(borda_scores(candidates, ballots), seats),
+            Self::InstantRunoff => {
+                let mut hopeful = candidates.to_vec();
+                let mut winners = vec![];
+                while winners.len() < seats {
+                    let winner = instant_runoff_winner(&hopeful, ballots);
+                    hopeful.retain(|c| *c != winner);
+                    winners.push(winner);
+                }
+                RankingResult {
+                    winners,
+                    scores: first_preferences(candidates, ballots),
+                }
+            }
+        }
+    }
+}
+
+fn approval_scores(candidates: &[Uint128], ballots: &[Vec<Uint128>]) -> Vec<(Uint128, u64)> {
+    candidates
+        .iter()
+        .map(|c| {
+            let approvals = ballots.iter().filter(|b| b.contains(c)).count() as u64;
+            (*c, approvals)
+        })
+        .collect()
+}
+
+fn borda_scores(candidates: &[Uint128], ballots: &[Vec<Uint128>]) -> Vec<(Uint128, u64)> {
+    let n = candidates.len() as u64;
+    let mut scores: Vec<(Uint128, u64)> = candidates.iter().map(|c| (*c, 0)).collect();
+    for ballot in ballots {
+        let ranked = ballot.iter().filter(|p| candidates.contains(p));
+        for (position, proposal) in ranked.enumerate() {
+            if let Some(score) = scores.iter_mut().find(|(c, _)| c == proposal) {
+                score.1 += n - 1 - position as u64;
+            }
+        }
+    }
+    scores
+}
+
+// highest score first, the lower id wins ties
+fn by_score(scores: Vec<(Uint128, u64)>, seats: usize) -> RankingResult {
+    let mut ordered = scores.clone();
+    ordered.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    RankingResult {
+        winners: ordered.into_iter().take(seats).map(|(c, _)| c).collect(),
+        scores,
+    }
+}
+
+// each ballot counts for its most preferred candidate still in the race
+fn first_preferences(candidates: &[Uint128], ballots: &[Vec<Uint128>]) -> Vec<(Uint128, u64)> {
+    let mut counts: Vec<(Uint128, u64)> = candidates.iter().map(|c| (*c, 0)).collect();
+    for ballot in ballots {
+        if let Some(top) = ballot.iter().find(|p| candidates.contains(p)) {
+            if let Some(count) = counts.iter_mut().find(|(c, _)| c == top) {
+                count.1 += 1;
+            }
+        }
+    }
+    counts
+}
+
+// eliminates the weakest candidate until one holds a majority of the live ballots,
+// the higher id is eliminated first on ties
+fn instant_runoff_winner(candidates: &[Uint128], ballots: &[Vec<Uint128>]) -> Uint128 {
+    let mut hopeful = candidates.to_vec();
+    loop {
+        let counts = first_preferences(&hopeful, ballots);
+        let live: u64 = counts.iter().map(|(_, n)| n).sum();
+        let (leader, most) = counts
+            .iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .copied()
+            .unwrap();
+        if most * 2 > live || hopeful.len() == 1 {
+            return leader;
+        }
+        let (loser, _) = counts
+            .iter()
+            .min_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .copied()
+            .unwrap();
+        hopeful.retain(|c| *c != loser);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[u128]) -> Vec<Uint128> {
+        ids.iter().map(|id| Uint128::from(*id)).collect()
+    }
+
+    #[test]
+    fn test_approval() {
+        let ballots = vec![ids(&[1, 2]), ids(&[2, 3]), ids(&[2]), ids(&[3, 9])];
+        let result = RankingMethod::Approval.tally(&ids(&[1, 2, 3]), &ballots, 2);
+        assert_eq!(result.winners, ids(&[2, 3]));
+        assert_eq!(
+            result.scores,
+            vec![
+                (Uint128::from(1u128), 1),
+                (Uint128::from(2u128), 3),
+                (Uint128::from(3u128), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_borda() {
+        let ballots = vec![ids(&[1, 2, 3]), ids(&[1, 3, 2]), ids(&[2, 3, 1]), ids(&[3])];
+        let result = RankingMethod::Borda.tally(&ids(&[1, 2, 3]), &ballots, 1);
+        // 1: 2 + 2 + 0, 2: 1 + 0 + 2, 3: 0 + 1 + 1 + 2
+        assert_eq!(result.winners, ids(&[1]));
+        assert_eq!(
+            result.scores,
+            vec![
+                (Uint128::from(1u128), 4),
+                (Uint128::from(2u128), 3),
+                (Uint128::from(3u128), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_instant_runoff() {
+        // 3 is eliminated first and its ballots transfer to 2
+        let ballots = vec![
+            ids(&[1, 2]),
+            ids(&[1, 3]),
+            ids(&[2, 1]),
+            ids(&[2, 3]),
+            ids(&[3, 2]),
+        ];
+        let result = RankingMethod::InstantRunoff.tally(&ids(&[1, 2, 3]), &ballots, 2);
+        assert_eq!(result.winners, ids(&[2, 1]));
+        assert_eq!(
+            result.scores,
+            vec![
+                (Uint128::from(1u128), 2),
+                (Uint128::from(2u128), 2),
+                (Uint128::from(3u128), 1)
+            ]
+        );
+
+        // more seats than candidates
+        let result = RankingMethod::InstantRunoff.tally(&ids(&[1, 2]), &[], 5);
+        assert_eq!(result.winners, ids(&[1, 2]));
+    }
+}
@@ -0,0 +1,102 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Uint128;
+
+#[cw_serde]
+#[derive(Eq)]
+pub enum Aggregation {
+    Median,
+    Mean,
+}
+
+impl Aggregation {
+    // every ballot holds one amount per project. aggregates that add up to more
+    // than the budget are scaled down, returns the payouts and the leftover
+    pub fn allocate(
+        &self,
+        projects: usize,
+        ballots: &[Vec<u128>],
+        budget: u128,
+    ) -> (Vec<u128>, u128) {
+        let aggregated: Vec<u128> = (0..projects)
+            .map(|i| {
+                let amounts: Vec<u128> = ballots.iter().map(|b| b[i]).collect();
+                match self {
+                    Self::Median => median(amounts),
+                    Self::Mean => mean(&amounts),
+                }
+            })
+            .collect();
+
+        let total: u128 = aggregated.iter().sum();
+        let payouts: Vec<u128> = if total > budget {
+            aggregated
+                .into_iter()
+                .map(|a| Uint128::from(a).multiply_ratio(budget, total).u128())
+                .collect()
+        } else {
+            aggregated
+        };
+        let leftover = budget - payouts.iter().sum::<u128>();
+        (payouts, leftover)
+    }
+}
+
+// the mean of the two middle amounts for an even number of ballots
+fn median(mut amounts: Vec<u128>) -> u128 {
+    if amounts.is_empty() {
+        return 0;
+    }
+    amounts.sort_unstable();
+    let mid = amounts.len() / 2;
+    if amounts.len() % 2 == 1 {
+        amounts[mid]
+    } else {
+        (amounts[mid - 1] + amounts[mid]) / 2
+    }
+}
+
+fn mean(amounts: &[u128]) -> u128 {
+    if amounts.is_empty() {
+        return 0;
+    }
+    amounts.iter().sum::<u128>() / amounts.len() as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        let ballots = vec![vec![600, 400], vec![200, 300], vec![500, 0]];
+        let (payouts, leftover) = Aggregation::Median.allocate(2, &ballots, 1000);
+        assert_eq!(payouts, vec![500, 300]);
+        assert_eq!(leftover, 200);
+
+        let ballots = vec![vec![600, 400], vec![200, 300]];
+        let (payouts, leftover) = Aggregation::Median.allocate(2, &ballots, 1000);
+        assert_eq!(payouts, vec![400, 350]);
+        assert_eq!(leftover, 250);
+    }
+
+    #[test]
+    fn test_mean() {
+        let ballots = vec![vec![600, 400], vec![200, 300], vec![500, 0]];
+        let (payouts, leftover) = Aggregation::Mean.allocate(2, &ballots, 1000);
+        assert_eq!(payouts, vec![433, 233]);
+        assert_eq!(leftover, 334);
+    }
+
+    #[test]
+    fn test_scaled_to_budget() {
+        // medians add up to 1500, each is scaled by 1000 / 1500
+        let ballots = vec![vec![900, 600], vec![900, 600], vec![0, 0]];
+        let (payouts, leftover) = Aggregation::Median.allocate(2, &ballots, 1000);
+        assert_eq!(payouts, vec![600, 400]);
+        assert_eq!(leftover, 0);
+
+        let (payouts, leftover) = Aggregation::Mean.allocate(2, &[], 1000);
+        assert_eq!(payouts, vec![0, 0]);
+        assert_eq!(leftover, 1000);
+    }
+}
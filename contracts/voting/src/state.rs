@@ -1,51 +1,95 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Binary, CosmosMsg, Decimal, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
 
+use crate::quadratic::{CalculatedGrant, QuadraticFundingAlgorithm};
+use crate::ranking::RankingMethod;
+use crate::retro::Aggregation;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct State {
     pub denom: String,
     pub owner: Addr,
+    // approves grant milestones
+    #[serde(default)]
+    pub reviewers: Vec<Addr>,
+    #[serde(default)]
+    pub ballot_mode: BallotMode,
+    #[serde(default)]
+    pub voter_eligibility: VoterEligibility,
+    #[serde(default)]
+    pub voting_mode: VotingMode,
+    // cw4 group whose weighted members review proposals, replaces staked reviews
+    #[serde(default)]
+    pub reviewer_group: Option<Addr>,
+    // tallying of approval and ranked ballots, none disables them
+    #[serde(default)]
+    pub ranking: Option<Ranking>,
+    #[serde(default)]
+    pub guardian: Option<Addr>,
+    #[serde(default)]
+    pub paused: bool,
+    // cut of vote payments and matching payouts, none charges nothing
+    #[serde(default)]
+    pub fee: Option<PlatformFee>,
+    // share of the reviewers that must approve a milestone before it is released
+    #[serde(default = "default_milestone_threshold")]
+    pub milestone_threshold: Decimal,
+    // match weight of contributors with neither a set trust weight nor a verified identity
+    #[serde(default = "Decimal::one")]
+    pub default_trust_weight: Decimal,
+    // stake of a single vote, the n-th vote on a proposal costs n^2 of it
+    #[serde(default = "default_unit_price")]
+    pub unit_price: Uint128,
     //pub staked_tokens:Uint128
 }
 
+pub fn default_milestone_threshold() -> Decimal {
+    Decimal::percent(50)
+}
+
+pub fn default_unit_price() -> Uint128 {
+    Uint128::new(1000)
+}
+
 
 #[cw_serde]
+#[derive(Default)]
 pub enum ProposalStatus {
+    #[default]
     PreQual,
     InProgress,
     Reviewed,
     Passed,
     Rejected,
+    // commit-reveal ballots, votes are counted as they are revealed
+    Revealing,
+    // a passed proposal whose messages were dispatched by ExecuteProposal
+    Executed,
 
 }
 
-impl Default for ProposalStatus {
-    fn default() -> Self {
-    
-        Self::PreQual
-    }
-
-}
-
-
-
 #[cw_serde]
 pub struct Voter {
     //pub is_voted: bool,
     //Vec (proposal_id, number of votes)
     pub vote_count: Vec<(Uint128,u64)>,
+    // (proposal_id, number of no-votes) among vote_count
+    #[serde(default)]
+    pub against: Vec<(Uint128, u64)>,
 }
 
 
 #[cw_serde]
 #[derive(Default)]
 pub struct BalanceVote {
-    pub token_balance: Uint128,             // total staked balance
-    pub locked_tokens: Vec<(u64, Uint128)>, //maps poll_id to weight voted
-    pub participated_polls: Vec<u64>,       // poll_id
+    pub token_balance: Uint128,                 // total staked balance
+    pub locked_tokens: Vec<(Uint128, Uint128)>, //maps proposal_id to tokens paid for votes
+    #[serde(default)]
+    pub locked_against: Vec<(Uint128, Uint128)>, // part of locked_tokens paid for no-votes
+    pub participated_polls: Vec<u64>,           // poll_id
 }
 
 
@@ -57,6 +101,274 @@ pub struct Proposal {
     pub yes_votes: Uint128,
     pub no_votes: Uint128,
     pub voters: Vec<Addr>,
+    #[serde(default)]
+    pub round_id: Option<Uint128>,
+    #[serde(default)]
+    pub reveal_ends: Option<Timestamp>,
+    // block height voting power is read at in VotingMode::Snapshot
+    #[serde(default)]
+    pub start_height: u64,
+    // block height the review committee is read at, set when the vote ends
+    #[serde(default)]
+    pub review_height: u64,
+    // total weight of the reviewer group when the vote ended
+    #[serde(default)]
+    pub review_weight: u64,
+    // the vote on a parameter change can be ended by anyone after this, not before
+    #[serde(default)]
+    pub vote_ends: Option<Timestamp>,
+    // the same for the review of a parameter change
+    #[serde(default)]
+    pub review_ends: Option<Timestamp>,
+}
+
+#[cw_serde]
+#[derive(Default, Eq)]
+pub enum BallotMode {
+    #[default]
+    Open,
+    // votes are committed as sha256 hashes and counted on reveal
+    CommitReveal {
+        reveal_period: u64, // seconds after end_vote
+        unrevealed: UnrevealedPolicy,
+    },
+}
+
+#[cw_serde]
+#[derive(Default, Eq)]
+pub enum VotingMode {
+    // the n-th vote on a proposal costs n^2 stake units
+    #[default]
+    Quadratic,
+    // the tally weight equals the tokens staked
+    Linear,
+    // one free vote per allowlisted voter
+    OnePersonOneVote,
+    // holders vote with their balance at the proposal's start height,
+    // read from a contract answering SnapshotQueryMsg::BalanceAtHeight
+    Snapshot { contract: Addr },
+    // stake on a proposal builds conviction every block, keeping `decay` of the
+    // previous value, and the request is paid out once conviction crosses the
+    // threshold, see ProposeFunding
+    Conviction { decay: Decimal, threshold: Decimal },
+}
+
+#[cw_serde]
+#[derive(Eq)]
+pub struct PlatformFee {
+    pub bps: u16, // basis points, capped by MAX_FEE_BPS
+    pub treasury: Addr,
+}
+
+pub const MAX_FEE_BPS: u16 = 1000;
+
+// seconds the vote and the review of a parameter change stay open
+pub const PARAMETER_VOTING_PERIOD: u64 = 7 * 24 * 60 * 60;
+pub const PARAMETER_REVIEW_PERIOD: u64 = 7 * 24 * 60 * 60;
+
+#[cw_serde]
+#[derive(Eq)]
+pub struct Ranking {
+    pub method: RankingMethod,
+    // number of proposals picked per round
+    pub winners: u32,
+}
+
+#[cw_serde]
+pub enum Ballot {
+    Approval { approved: Vec<Uint128> },
+    // most preferred first
+    Ranked { ranking: Vec<Uint128> },
+}
+
+#[cw_serde]
+pub struct Conviction {
+    pub requested: Uint128,
+    pub staked: Uint128,
+    pub conviction: Uint128,
+    pub last_height: u64,
+}
+
+impl Conviction {
+    // after n blocks at stake x: y = decay^n * y0 + x * (1 - decay^n) / (1 - decay)
+    pub fn accrue(&mut self, height: u64, decay: Decimal) {
+        let blocks = height.saturating_sub(self.last_height).min(u32::MAX as u64) as u32;
+        let retained = decay.pow(blocks);
+        self.conviction = self.conviction * retained
+            + self.staked * ((Decimal::one() - retained) / (Decimal::one() - decay));
+        self.last_height = height;
+    }
+}
+
+// applied by the contract itself once its proposal passes end_review
+#[cw_serde]
+pub enum ParameterChange {
+    UpdateConfig {
+        ballot_mode: Option<BallotMode>,
+        voter_eligibility: Option<VoterEligibility>,
+        voting_mode: Option<VotingMode>,
+        ranking: Option<Ranking>,
+    },
+    UpdateReviewers {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    UpdateMilestoneThreshold {
+        threshold: Decimal,
+    },
+    UpdateUnitPrice {
+        unit_price: Uint128,
+    },
+    Execute { msgs: Vec<CosmosMsg> },
+}
+
+#[cw_serde]
+#[derive(Default, Eq)]
+pub enum VoterEligibility {
+    #[default]
+    Open,
+    // addresses added by the owner with UpdateAllowlist
+    Allowlist,
+    // external registry answering RegistryQueryMsg::IsVerified
+    Registry { contract: Addr },
+}
+
+#[cw_serde]
+#[derive(Eq)]
+pub enum UnrevealedPolicy {
+    // deposit goes to the owner
+    Forfeit,
+    Refund,
+}
+
+#[cw_serde]
+pub struct Commitment {
+    pub commitment: Binary,
+    pub deposit: Uint128,
+}
+
+#[cw_serde]
+pub struct Round {
+    pub proposals: Vec<Uint128>,
+    pub budget: Uint128,
+    pub algorithm: QuadraticFundingAlgorithm,
+    // grants can only be claimed into a milestone escrow
+    #[serde(default)]
+    pub milestones_required: bool,
+    // grants without milestones are streamed instead of paid at once
+    #[serde(default)]
+    pub vesting: Option<Vesting>,
+    // badgeholders split the budget instead of the matching algorithm
+    #[serde(default)]
+    pub retro: Option<RetroFunding>,
+}
+
+#[cw_serde]
+pub struct RetroFunding {
+    pub badgeholders: Vec<Addr>,
+    pub aggregation: Aggregation,
+}
+
+#[cw_serde]
+pub struct Vesting {
+    pub start: Timestamp,
+    pub end: Timestamp,
+}
+
+#[cw_serde]
+pub struct Stream {
+    pub round_id: Uint128,
+    pub amount: Uint128,
+    pub claimed: Uint128,
+    pub vesting: Vesting,
+}
+
+impl Stream {
+    // linear between start and end
+    pub fn vested(&self, now: Timestamp) -> Uint128 {
+        let Vesting { start, end } = &self.vesting;
+        if now >= *end {
+            self.amount
+        } else if now <= *start {
+            Uint128::zero()
+        } else {
+            self.amount.multiply_ratio(
+                now.seconds() - start.seconds(),
+                end.seconds() - start.seconds(),
+            )
+        }
+    }
+}
+
+#[cw_serde]
+pub struct Distribution {
+    pub grants: Vec<GrantPayout>,
+    pub leftover: Uint128,
+}
+
+#[cw_serde]
+pub struct DelegatedVote {
+    pub yes: bool,
+    // taken from the delegator's credits and locked like a vote of their own
+    pub stake: Uint128,
+}
+
+#[cw_serde]
+pub enum MilestoneStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Claimed,
+}
+
+#[cw_serde]
+pub struct Milestone {
+    pub percentage: Decimal,
+    pub status: MilestoneStatus,
+    // reviewers who voted on the milestone while it was pending
+    #[serde(default)]
+    pub approvals: Vec<Addr>,
+    #[serde(default)]
+    pub rejections: Vec<Addr>,
+    // summed reviewer weights, one per reviewer unless a cw4 group reviews
+    #[serde(default)]
+    pub approved_weight: u64,
+    #[serde(default)]
+    pub rejected_weight: u64,
+}
+
+#[cw_serde]
+pub struct Escrow {
+    pub round_id: Uint128,
+    // grant plus collected funds, zero until the grant is claimed into escrow
+    pub amount: Uint128,
+    pub milestones: Vec<Milestone>,
+}
+
+impl Escrow {
+    // the last tranche takes the rounding remainder
+    pub fn tranche(&self, index: usize) -> Uint128 {
+        if index + 1 == self.milestones.len() {
+            let others: Uint128 = self.milestones[..index]
+                .iter()
+                .map(|m| self.amount * m.percentage)
+                .sum();
+            self.amount - others
+        } else {
+            self.amount * self.milestones[index].percentage
+        }
+    }
+}
+
+#[cw_serde]
+pub struct GrantPayout {
+    pub proposal_id: Uint128,
+    // the match is net of the fee
+    pub grant: CalculatedGrant,
+    // platform fee on the match, booked into the treasury when claimed
+    #[serde(default)]
+    pub fee: Uint128,
+    pub claimed: bool,
 }
 pub const STATE: Item<State> = Item::new("state");
 
@@ -65,3 +377,96 @@ pub const PROPOSALS: Map<&[u8], Proposal> = Map::new("proposals");
 pub const VOTERS: Map<&[u8],Voter> = Map::new("voter");
 
 pub const BALANCES: Map<&[u8], BalanceVote> = Map::new("balance");
+
+pub const ROUNDS: Map<&[u8], Round> = Map::new("rounds");
+
+pub const DISTRIBUTIONS: Map<&[u8], Distribution> = Map::new("distributions");
+
+pub const ESCROWS: Map<&[u8], Escrow> = Map::new("escrows");
+
+pub const STREAMS: Map<&[u8], Stream> = Map::new("streams");
+
+// delegator -> delegate
+pub const DELEGATIONS: Map<&[u8], Addr> = Map::new("delegations");
+
+// delegate -> delegators
+pub const DELEGATORS: Map<&[u8], Vec<Addr>> = Map::new("delegators");
+
+// (proposal_id, delegator) -> vote cast by the delegate
+pub const DELEGATED_VOTES: Map<(&[u8], &[u8]), DelegatedVote> = Map::new("delegated_votes");
+
+// delegator -> deposit paying for the quadratic votes cast through the delegate
+pub const DELEGATION_CREDITS: Map<&[u8], Uint128> = Map::new("delegation_credits");
+
+// (proposal_id, voter) -> sealed ballot
+pub const COMMITMENTS: Map<(&[u8], &[u8]), Commitment> = Map::new("commitments");
+
+// voters allowed in VoterEligibility::Allowlist mode
+pub const ALLOWLIST: Map<&[u8], bool> = Map::new("allowlist");
+
+// (proposal_id, reviewer) -> approved, for reviews through the cw4 group
+pub const REVIEWS: Map<(&[u8], &[u8]), bool> = Map::new("reviews");
+
+// (round_id, voter) -> ballot over the round's proposals
+pub const BALLOTS: Map<(&[u8], &[u8]), Ballot> = Map::new("ballots");
+
+// (proposal_id, amount) pairs of one badgeholder's ballot
+pub type Allocations = Vec<(Uint128, Uint128)>;
+
+// (round_id, badgeholder) -> allocations in a retro round
+pub const ALLOCATIONS: Map<(&[u8], &[u8]), Allocations> = Map::new("allocations");
+
+pub const CONVICTIONS: Map<&[u8], Conviction> = Map::new("convictions");
+
+// (proposal_id, voter) -> stake on a conviction proposal
+pub const CONVICTION_STAKES: Map<(&[u8], &[u8]), Uint128> = Map::new("conviction_stakes");
+
+// funds paid out to conviction proposals
+pub const CONVICTION_POOL: Item<Uint128> = Item::new("conviction_pool");
+
+// scales a contributor's square root term in the match, 0..=1
+pub const TRUST_WEIGHTS: Map<&[u8], Decimal> = Map::new("trust_weights");
+
+// proposal_id -> change governed by the proposal
+pub const PARAMETER_CHANGES: Map<&[u8], ParameterChange> = Map::new("parameter_changes");
+
+// proposal_id -> messages dispatched once the proposal passes
+pub const PROPOSAL_MSGS: Map<&[u8], Vec<CosmosMsg>> = Map::new("proposal_msgs");
+
+// fees collected and not yet withdrawn by the treasury
+pub const TREASURY: Item<Uint128> = Item::new("treasury");
+
+// balances as stored before proposal ids became Uint128, rewritten by migrate
+#[cw_serde]
+pub struct LegacyBalanceVote {
+    pub token_balance: Uint128,
+    pub locked_tokens: Vec<(u64, Uint128)>,
+    pub participated_polls: Vec<u64>,
+}
+
+#[cw_serde]
+#[serde(untagged)]
+pub enum StoredBalanceVote {
+    Current(BalanceVote),
+    Legacy(LegacyBalanceVote),
+}
+
+impl From<StoredBalanceVote> for BalanceVote {
+    fn from(stored: StoredBalanceVote) -> Self {
+        match stored {
+            StoredBalanceVote::Current(balance) => balance,
+            StoredBalanceVote::Legacy(legacy) => BalanceVote {
+                token_balance: legacy.token_balance,
+                locked_tokens: legacy
+                    .locked_tokens
+                    .into_iter()
+                    .map(|(id, amount)| (Uint128::from(id), amount))
+                    .collect(),
+                locked_against: vec![],
+                participated_polls: legacy.participated_polls,
+            },
+        }
+    }
+}
+
+pub const STORED_BALANCES: Map<&[u8], StoredBalanceVote> = Map::new("balance");